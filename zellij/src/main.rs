@@ -1,24 +1,774 @@
-use serde::Deserialize;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use zellij_tile::prelude::*;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-#[derive(Debug, Deserialize)]
+/// Placeholders provided by the plugin; custom vars with these names are ignored
+const BUILTIN_PLACEHOLDERS: &[&str] = &[
+    "tab_position",
+    "cwd_basename",
+    "active_marker",
+    "sync_marker",
+    "dup_index",
+    "age_minutes",
+    "prev_tab_name",
+    "next_tab_name",
+    "visible_index",
+    "git_branch",
+    "group_index",
+    "rename_count",
+    "pane_count",
+    "pane_exit_code",
+    "layout_marker",
+    "active_pane_title",
+    "ordinal_word",
+    "tab_position_from_end",
+    "project_dir",
+    "date",
+    "weekday",
+    "pane_pid",
+    "pane_args",
+    "session_short",
+];
+
+/// Placeholders rename-dynamic renders from the state of all tabs
+const AGGREGATE_PLACEHOLDERS: &[&str] = &[
+    "total_tabs",
+    "total_panes",
+    "active_tab_name",
+    "active_tab_position",
+    "tab_names",
+];
+
+/// Maximum number of names kept per tab in the rename history
+const MAX_HISTORY_LEN: usize = 20;
+
+/// Maximum number of consecutive re-renders a tab gets purely because its
+/// neighbors' names changed, so two tabs referencing each other can't loop forever
+const MAX_NEIGHBOR_CASCADE: u32 = 3;
+
+/// Maximum number of renames a single auto-update pass may issue. Tabs beyond the
+/// cap keep their stale position tracking and are picked up by the next event.
+const MAX_AUTO_RENAMES_PER_UPDATE: usize = 100;
+
+/// Substituted for {date} and {weekday} when the clock is unavailable
+const UNKNOWN_DATE: &str = "?";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How long an error from a keybinding-triggered command stays on screen
+/// with notify_on_error
+const ERROR_BANNER_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Deserialize)]
 struct RenamePayload {
-    pane_id: String,
-    name: String,
+    /// The pane whose tab should be renamed
+    #[serde(default)]
+    pane_id: Option<String>,
+    /// Alternatively, target the tab at this display index (0-indexed)
+    #[serde(default)]
+    tab_position: Option<usize>,
+    /// Alternatively, target the tab with this user-facing number (1-indexed,
+    /// as rendered by {tab_position})
+    #[serde(default)]
+    tab_number: Option<usize>,
+    /// With pane_id, only rename if the pane is still in the tab at this display index
+    #[serde(default)]
+    expected_tab_position: Option<usize>,
+    /// Alternatively, target the tab at focused position + offset
+    #[serde(default)]
+    tab_offset: Option<i32>,
+    /// Clamp an out-of-range tab_offset to the first/last tab instead of erroring
+    #[serde(default)]
+    clamp: bool,
+    /// Alternatively, rename every tab whose current name matches this glob
+    #[serde(default)]
+    match_pattern: Option<String>,
+    /// Also apply the template to every other tab that had the target tab's old name
+    #[serde(default)]
+    propagate_to_same_name: bool,
+    /// Alternatively, target the tab of the first pane whose title contains this text
+    #[serde(default)]
+    pane_title_contains: Option<String>,
+    /// Format string for the new name. Required unless template_file is given.
+    #[serde(default)]
+    name: Option<String>,
+    /// Read the format string from this file instead of name
+    #[serde(default)]
+    template_file: Option<String>,
+    /// Format strings keyed by pane count conditions ("1", ">1", "<=3", ...),
+    /// used instead of name when the tab's pane count matches
+    #[serde(default)]
+    templates: Option<BTreeMap<String, String>>,
+    /// Format string prepended to the formatted name
+    #[serde(default)]
+    prefix: String,
+    /// Format string appended to the formatted name
+    #[serde(default)]
+    suffix: String,
     /// Use stable tab ID tracking (default: true)
     /// Set to false to use tab.position directly (will break after tab deletion until Zellij #3535 is fixed)
     #[serde(default = "default_use_stable_ids")]
     use_stable_ids: bool,
+    /// Also resolve panes that are currently suppressed (e.g. swapped out by a swap layout)
+    #[serde(default)]
+    include_suppressed: bool,
+    /// Interpret pane_id as the id of a plugin pane instead of a terminal pane
+    #[serde(default)]
+    allow_plugin_pane: bool,
+    /// Working directory of the pane, used for the {cwd_basename} placeholder
+    #[serde(default)]
+    cwd: Option<String>,
+    /// Working directories by pane id, remembered for the {project_dir} placeholder
+    #[serde(default)]
+    cwds: Option<BTreeMap<String, String>>,
+    /// Substituted for {active_marker} when the tab is active
+    #[serde(default = "default_active_symbol")]
+    active_symbol: String,
+    /// Substituted for {active_marker} when the tab is not active
+    #[serde(default)]
+    inactive_symbol: String,
+    /// Substituted for {sync_marker} when the tab is syncing input to all its panes
+    #[serde(default = "default_sync_symbol")]
+    sync_symbol: String,
+    /// Substituted for {sync_marker} when sync is off
+    #[serde(default)]
+    no_sync_symbol: String,
+    /// Substituted for {layout_marker} while a pane of the tab is fullscreen
+    #[serde(default = "default_fullscreen_symbol")]
+    fullscreen_symbol: String,
+    /// Substituted for {layout_marker} while the tab shows floating panes
+    #[serde(default = "default_floating_symbol")]
+    floating_symbol: String,
+    /// Give the first of several identically named tabs a {dup_index} of 1 instead of empty
+    #[serde(default)]
+    number_first_duplicate: bool,
+    /// Separator between a tab name's group and the rest, used by {group_index}
+    #[serde(default = "default_group_delimiter")]
+    group_delimiter: String,
+    /// Evaluate the template once and store the resulting name as a literal,
+    /// so later position changes don't re-render it
+    #[serde(default)]
+    freeze_after_apply: bool,
+    /// Hold the rename back until the tab is focused, then apply it
+    #[serde(default)]
+    defer_until_active: bool,
+    /// Custom variables available as placeholders. Built-in placeholders take precedence.
+    #[serde(default)]
+    vars: Option<BTreeMap<String, String>>,
+    /// Numeric formatting spec per custom var, e.g. {"load": ".1"}
+    #[serde(default)]
+    number_format: Option<BTreeMap<String, String>>,
+    /// Reject vars whose names collide with a built-in placeholder
+    #[serde(default)]
+    strict: bool,
+    /// Refuse to give the tab a name another tab already has
+    #[serde(default)]
+    unique: bool,
+    /// What unique does when the name is taken
+    #[serde(default)]
+    on_collision: OnCollision,
+    /// Abort unless the tab ID the rename would use equals this value
+    #[serde(default)]
+    verify_tab_id: Option<u32>,
+    /// Case transformation applied to the rendered name
+    #[serde(default)]
+    case: Option<NameCase>,
+    /// Truncate the name to this many terminal columns
+    #[serde(default)]
+    max_columns: Option<usize>,
+    /// How max_columns shortens a name that is too long
+    #[serde(default)]
+    truncate_mode: TruncateMode,
+    /// Separator between the segments dropped by the segments truncate_mode
+    #[serde(default = "default_truncate_separator")]
+    truncate_separator: String,
+    /// Remember the renamed tab as the home tab, reported by the home-tab pipe
+    #[serde(default)]
+    set_as_home: bool,
+    /// Only act when the plugin runs in the session with this name
+    #[serde(default)]
+    session: Option<String>,
+    /// Who asked for the rename, echoed in the response, history and logs.
+    /// Defaults to the name of the pipe.
+    #[serde(default)]
+    source: Option<String>,
+    /// strftime format of the {date} placeholder
+    #[serde(default = "default_date_format")]
+    date_format: String,
+    /// Re-render {date} and {weekday} when the day changes, instead of keeping the
+    /// date of the rename
+    #[serde(default)]
+    live_date: bool,
+    /// Not supported, since Zellij can't color tab names. Only parsed so a payload
+    /// using it is rejected with UNSUPPORTED instead of silently ignored.
+    #[serde(default)]
+    color_rules: Option<serde::de::IgnoredAny>,
+}
+
+/// A condition on a tab's pane count, written `N`, `>N`, `>=N`, `<N` or `<=N`
+#[derive(Debug, Clone, Copy, Serialize)]
+enum PaneCountCondition {
+    Exactly(usize),
+    MoreThan(usize),
+    AtLeast(usize),
+    FewerThan(usize),
+    AtMost(usize),
+}
+
+impl PaneCountCondition {
+    fn matches(self, pane_count: usize) -> bool {
+        match self {
+            PaneCountCondition::Exactly(n) => pane_count == n,
+            PaneCountCondition::MoreThan(n) => pane_count > n,
+            PaneCountCondition::AtLeast(n) => pane_count >= n,
+            PaneCountCondition::FewerThan(n) => pane_count < n,
+            PaneCountCondition::AtMost(n) => pane_count <= n,
+        }
+    }
+}
+
+impl FromStr for PaneCountCondition {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (condition, n): (fn(usize) -> Self, &str) = if let Some(n) = value.strip_prefix(">=") {
+            (PaneCountCondition::AtLeast, n)
+        } else if let Some(n) = value.strip_prefix("<=") {
+            (PaneCountCondition::AtMost, n)
+        } else if let Some(n) = value.strip_prefix('>') {
+            (PaneCountCondition::MoreThan, n)
+        } else if let Some(n) = value.strip_prefix('<') {
+            (PaneCountCondition::FewerThan, n)
+        } else {
+            (PaneCountCondition::Exactly, value)
+        };
+        n.trim().parse().map(condition).map_err(|_| ())
+    }
+}
+
+/// How a tab's panes are currently arranged, for {layout_marker}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum TabLayout {
+    Tiled,
+    Floating,
+    Fullscreen,
+}
+
+/// How a unique rename handles a name that another tab already has
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OnCollision {
+    /// Fail with NAME_COLLISION
+    #[default]
+    Error,
+    /// Append the smallest number (from 2) that makes the name unique
+    Suffix,
+}
+
+/// How a name longer than max_columns is shortened
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TruncateMode {
+    /// Cut off the characters past the limit
+    #[default]
+    Chars,
+    /// Drop leading separator-delimited segments, keeping the last one
+    Segments,
+}
+
+/// Case transformation applied to a rendered tab name
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum NameCase {
+    Upper,
+    Lower,
+    /// Uppercase the first letter of every whitespace-separated word, lowercase the rest
+    Title,
+}
+
+impl NameCase {
+    fn apply(self, name: &str) -> String {
+        match self {
+            NameCase::Upper => name.to_uppercase(),
+            NameCase::Lower => name.to_lowercase(),
+            NameCase::Title => {
+                let mut out = String::with_capacity(name.len());
+                let mut word_start = true;
+                for c in name.chars() {
+                    if word_start {
+                        out.extend(c.to_uppercase());
+                    } else {
+                        out.extend(c.to_lowercase());
+                    }
+                    word_start = c.is_whitespace();
+                }
+                out
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PaneQueryPayload {
+    pane_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShiftRenumberPayload {
+    /// Display index (0-indexed) of the first tab to rename
+    start_position: usize,
+    format: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CycleNamePayload {
+    pane_id: String,
+    /// Format strings the tab's name rotates through
+    names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameByIdPayload {
+    items: Vec<RenameByIdItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameByIdItem {
+    stable_id: u32,
+    /// Format string for the tab with this stable ID
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameAllPayload {
+    /// Format strings assigned to tabs in display order
+    names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TabQueryPayload {
+    #[serde(default)]
+    pane_id: Option<String>,
+    /// Display index (0-indexed)
+    #[serde(default)]
+    tab_position: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapNamesPayload {
+    first: TabQueryPayload,
+    second: TabQueryPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct StableIdQueryPayload {
+    stable_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetStableIdPayload {
+    pane_id: String,
+    stable_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetAutoUpdatePayload {
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetAutoUpdateForPayload {
+    pane_id: String,
+    enabled: bool,
+}
+
+/// An error reported back to the sender of a pipe command
+#[derive(Debug, Clone)]
+struct PipeError {
+    /// Machine-readable error code, e.g. "PANE_NOT_FOUND"
+    code: &'static str,
+    message: String,
+}
+
+impl PipeError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        PipeError {
+            code,
+            message: message.into(),
+        }
+    }
 }
 
 fn default_use_stable_ids() -> bool {
     true
 }
 
+fn default_active_symbol() -> String {
+    "●".to_string()
+}
+
+fn default_group_delimiter() -> String {
+    "/".to_string()
+}
+
+fn default_truncate_separator() -> String {
+    "/".to_string()
+}
+
+fn default_sync_symbol() -> String {
+    "⇶".to_string()
+}
+
+fn default_fullscreen_symbol() -> String {
+    "⛶".to_string()
+}
+
+fn default_floating_symbol() -> String {
+    "⧉".to_string()
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+/// A tab name template along with the payload values needed to evaluate it.
+/// Stored per stable tab ID so auto-update can re-render the name later.
+#[derive(Debug, Clone, Serialize)]
+struct TabFormat {
+    format_str: String,
+    /// Alternatives to format_str by pane count; the first matching one is used
+    templates: Vec<(PaneCountCondition, String)>,
+    prefix: String,
+    suffix: String,
+    cwd: Option<String>,
+    active_symbol: String,
+    inactive_symbol: String,
+    sync_symbol: String,
+    no_sync_symbol: String,
+    fullscreen_symbol: String,
+    floating_symbol: String,
+    number_first_duplicate: bool,
+    group_delimiter: String,
+    vars: BTreeMap<String, String>,
+    case: Option<NameCase>,
+    max_columns: Option<usize>,
+    truncate_mode: TruncateMode,
+    truncate_separator: String,
+    date_format: String,
+    live_date: bool,
+    /// The day of the rename (`%Y-%m-%d`), rendered by {date} unless live_date is set.
+    /// None when the clock was unavailable.
+    date: Option<String>,
+}
+
+impl TabFormat {
+    /// Build a format from a rename payload. With custom delimiters, the templates
+    /// are translated to `{}` syntax so everything downstream only deals with one form.
+    fn from_payload(
+        rename_payload: &RenamePayload,
+        format_str: &str,
+        delimiters: Option<&(String, String)>,
+    ) -> Self {
+        let template = |template: &str| match delimiters {
+            Some((open, close)) => translate_delimiters(template, open, close),
+            None => template.to_string(),
+        };
+
+        TabFormat {
+            format_str: template(format_str),
+            templates: Vec::new(),
+            prefix: template(&rename_payload.prefix),
+            suffix: template(&rename_payload.suffix),
+            cwd: rename_payload.cwd.clone(),
+            active_symbol: rename_payload.active_symbol.clone(),
+            inactive_symbol: rename_payload.inactive_symbol.clone(),
+            sync_symbol: rename_payload.sync_symbol.clone(),
+            no_sync_symbol: rename_payload.no_sync_symbol.clone(),
+            fullscreen_symbol: rename_payload.fullscreen_symbol.clone(),
+            floating_symbol: rename_payload.floating_symbol.clone(),
+            number_first_duplicate: rename_payload.number_first_duplicate,
+            group_delimiter: rename_payload.group_delimiter.clone(),
+            vars: rename_payload.vars.clone().unwrap_or_default(),
+            case: rename_payload.case,
+            max_columns: rename_payload.max_columns,
+            truncate_mode: rename_payload.truncate_mode,
+            truncate_separator: rename_payload.truncate_separator.clone(),
+            date_format: rename_payload.date_format.clone(),
+            live_date: rename_payload.live_date,
+            date: current_date().map(|date| date.to_string()),
+        }
+    }
+
+    /// A format that always renders to the given name, with braces escaped
+    fn literal(name: &str) -> Self {
+        TabFormat {
+            format_str: name.replace('{', "{{").replace('}', "}}"),
+            templates: Vec::new(),
+            prefix: String::new(),
+            suffix: String::new(),
+            cwd: None,
+            active_symbol: String::new(),
+            inactive_symbol: String::new(),
+            sync_symbol: String::new(),
+            no_sync_symbol: String::new(),
+            fullscreen_symbol: String::new(),
+            floating_symbol: String::new(),
+            number_first_duplicate: false,
+            group_delimiter: default_group_delimiter(),
+            vars: BTreeMap::new(),
+            case: None,
+            max_columns: None,
+            truncate_mode: TruncateMode::Chars,
+            truncate_separator: default_truncate_separator(),
+            date_format: default_date_format(),
+            live_date: false,
+            date: None,
+        }
+    }
+
+    /// Whether the format string, its alternative templates or its prefix/suffix
+    /// reference the given placeholder
+    fn uses_placeholder(&self, placeholder: &str) -> bool {
+        let needle = format!("{{{}", placeholder);
+        [&self.prefix, &self.format_str, &self.suffix]
+            .iter()
+            .copied()
+            .chain(self.templates.iter().map(|(_, template)| template))
+            .any(|s| s.contains(&needle))
+    }
+
+    /// Length in characters of the longest template this format can render: the
+    /// prefix and suffix around the longest of format_str and its alternatives
+    fn template_length(&self) -> usize {
+        let longest = std::iter::once(&self.format_str)
+            .chain(self.templates.iter().map(|(_, template)| template))
+            .map(|template| template.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.prefix.chars().count() + longest + self.suffix.chars().count()
+    }
+
+    /// Whether the name shows the date and should follow it when the day changes
+    fn follows_date(&self) -> bool {
+        self.live_date && (self.uses_placeholder("date") || self.uses_placeholder("weekday"))
+    }
+
+    /// The day {date} and {weekday} are rendered for
+    fn render_date(&self) -> Option<chrono::NaiveDate> {
+        if self.live_date {
+            current_date()
+        } else {
+            self.date.as_deref()?.parse().ok()
+        }
+    }
+
+    /// The format string to render for a tab with the given number of panes
+    fn format_str_for(&self, pane_count: usize) -> &str {
+        self.templates
+            .iter()
+            .find(|(condition, _)| condition.matches(pane_count))
+            .map_or(&self.format_str, |(_, template)| template)
+    }
+}
+
+/// Plugin configuration, read from the plugin's KDL config block in load()
+#[derive(Debug)]
+struct Config {
+    /// Track suppressed panes in pane_to_tab like any other pane
+    include_suppressed: bool,
+    /// Minimum time between two rename_tab calls for the same tab (0 disables)
+    rename_interval: Duration,
+    /// Don't log errors to stderr (they are still returned over the pipe)
+    quiet: bool,
+    /// Custom placeholder delimiters replacing `{` and `}`, if configured
+    delimiters: Option<(String, String)>,
+    /// Most verbose diagnostics written to stderr
+    log_level: LogLevel,
+    /// Skip auto-update while fewer tabs than this are open (0 disables)
+    min_tabs_for_autoupdate: usize,
+    /// Plain text wrapped around every name the plugin sets
+    global_prefix: String,
+    global_suffix: String,
+    /// Rebuilds a stable tab ID may hold state without belonging to any tab
+    /// before that state is dropped (0 disables the sweep)
+    stale_id_gc_rebuilds: u32,
+    /// Count {tab_position_from_end} from 0 (last tab = 0) instead of 1
+    position_from_end_zero_based: bool,
+    /// File every applied rename is appended to, as one JSON object per line
+    log_file: Option<String>,
+    /// Name given by reset-to-default; {tab_position} is the 1-indexed position
+    default_tab_name: String,
+    /// Number of characters of the session name rendered by {session_short}
+    session_short_length: usize,
+    /// Longest template a rename may use, in characters; 0 disables the limit
+    max_template_length: usize,
+    /// Show errors of keybinding-triggered commands in the plugin's pane
+    notify_on_error: bool,
+    /// Number stable IDs from 1 again once no tab is tracked, instead of
+    /// continuing above the highest ID handed out so far
+    reset_ids_when_empty: bool,
+}
+
+/// Verbosity of stderr diagnostics, from least to most verbose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Debug builds log everything, release builds only errors and warnings
+    fn default_for_build() -> Self {
+        if cfg!(debug_assertions) {
+            LogLevel::Debug
+        } else {
+            LogLevel::Warn
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Config {
+    /// Parse the plugin configuration. Problems with it are returned as warnings
+    /// rather than logged, because the log level is only known once this returns.
+    fn from_configuration(configuration: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let config = Config {
+            include_suppressed: parse_bool(configuration, "include_suppressed"),
+            rename_interval: Duration::from_millis(parse_value(
+                configuration,
+                &mut warnings,
+                "rename_interval_ms",
+                100,
+            )),
+            quiet: parse_bool(configuration, "quiet"),
+            delimiters: parse_delimiters(configuration, &mut warnings),
+            log_level: parse_value(
+                configuration,
+                &mut warnings,
+                "log_level",
+                LogLevel::default_for_build(),
+            ),
+            min_tabs_for_autoupdate: parse_value(
+                configuration,
+                &mut warnings,
+                "min_tabs_for_autoupdate",
+                0,
+            ),
+            stale_id_gc_rebuilds: parse_value(
+                configuration,
+                &mut warnings,
+                "stale_id_gc_rebuilds",
+                10,
+            ),
+            position_from_end_zero_based: parse_bool(configuration, "position_from_end_zero_based"),
+            notify_on_error: parse_bool(configuration, "notify_on_error"),
+            reset_ids_when_empty: parse_bool(configuration, "reset_ids_when_empty"),
+            session_short_length: parse_value(
+                configuration,
+                &mut warnings,
+                "session_short_length",
+                3,
+            ),
+            max_template_length: parse_value(
+                configuration,
+                &mut warnings,
+                "max_template_length",
+                512,
+            ),
+            default_tab_name: configuration
+                .get("default_tab_name")
+                .cloned()
+                .unwrap_or_else(|| "Tab #{tab_position}".to_string()),
+            log_file: configuration
+                .get("log_file")
+                .filter(|path| !path.is_empty())
+                .cloned(),
+            global_prefix: configuration
+                .get("global_prefix")
+                .cloned()
+                .unwrap_or_default(),
+            global_suffix: configuration
+                .get("global_suffix")
+                .cloned()
+                .unwrap_or_default(),
+        };
+        (config, warnings)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::from_configuration(&BTreeMap::new()).0
+    }
+}
+
+/// A tab auto-update would re-render, see State::auto_update_candidates
+struct AutoUpdateCandidate {
+    stable_tab_id: u32,
+    tab_position: usize,
+    tab_format: TabFormat,
+    /// Only a neighbor's name changed, which counts towards MAX_NEIGHBOR_CASCADE
+    neighbor_only: bool,
+}
+
+/// Counters reported by the metrics pipe, since the plugin was loaded
+#[derive(Debug, Default, Serialize)]
+struct Metrics {
+    /// Calls to rename_tab, from any trigger
+    renames_applied: u64,
+    /// Pipe renames that found the tab already showing the requested name
+    renames_skipped: u64,
+    /// Renames issued by auto-update (also counted in renames_applied once sent)
+    auto_updates_triggered: u64,
+    /// Failed pipe commands by error code
+    errors_by_code: BTreeMap<&'static str, u64>,
+}
+
+/// The interactive tab renamer drawn by render() while it is open
+#[derive(Debug, Default)]
+struct RenameMenu {
+    /// Display index of the highlighted tab
+    selected: usize,
+    /// The new name being typed, once a tab was picked
+    input: Option<String>,
+    /// Why the last rename from the menu failed
+    error: Option<String>,
+}
+
 #[derive(Default)]
 struct State {
+    /// Configuration passed to the plugin on load
+    config: Config,
+
     /// The tabs currently open in the terminal
     tabs: Vec<TabInfo>,
 
@@ -39,470 +789,6231 @@ struct State {
     /// (for when Zellij fixes the issue)
     pane_to_stable_tab_id: BTreeMap<u32, u32>,
 
-    /// AUTO-UPDATE: Stores the original format string (with {tab_position} placeholder)
+    /// Stable tab IDs for tabs with no tracked panes (e.g. plugin-only tabs),
+    /// keyed by display index since there is no pane to key them off
+    paneless_tab_to_stable_id: BTreeMap<usize, u32>,
+
+    /// Highest stable tab ID of a tab with panes seen at the end of a rebuild, so
+    /// numbering can continue from it once no tab is tracked (unless
+    /// reset_ids_when_empty)
+    stable_id_high_water: u32,
+
+    /// Stable tab ID of the tab at each display index, kept in sync with the maps
+    /// above by refresh_position_to_stable_tab_id so lookups don't scan them
+    position_to_stable_tab_id: Vec<Option<u32>>,
+
+    /// AUTO-UPDATE: Stores the original format (with {tab_position} placeholder)
     /// per stable tab ID. When a tab's position changes, we re-evaluate and rename.
-    stable_tab_id_to_format_str: BTreeMap<u32, String>,
+    stable_tab_id_to_format: BTreeMap<u32, TabFormat>,
 
     /// Tracks the last known display position for each stable tab ID
     /// Used to detect when positions change and trigger re-evaluation
     stable_tab_id_to_last_position: BTreeMap<u32, usize>,
-}
 
-register_plugin!(State);
+    /// Tracks the last known active state for each stable tab ID
+    /// Used to re-render names using {active_marker} when focus moves between tabs
+    stable_tab_id_to_last_active: BTreeMap<u32, bool>,
 
-impl ZellijPlugin for State {
-    fn load(&mut self, _configuration: BTreeMap<String, String>) {
-        request_permission(&[
-            PermissionType::ReadApplicationState,
-            PermissionType::ChangeApplicationState,
-        ]);
-        subscribe(&[EventType::TabUpdate, EventType::PaneUpdate]);
-    }
+    /// Tracks the last known sync state for each stable tab ID
+    /// Used to re-render names using {sync_marker} when input sync is toggled
+    stable_tab_id_to_last_sync: BTreeMap<u32, bool>,
 
-    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
-        #[cfg(debug_assertions)]
-        eprintln!(
-            "PLUGIN: Received pipe message: name='{}', has_payload={}",
-            pipe_message.name,
-            pipe_message.payload.is_some()
-        );
-
-        // Only handle messages for our named pipe
-        if pipe_message.name != "change-tab-name" {
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "PLUGIN: Ignoring pipe '{}' (not 'change-tab-name')",
-                pipe_message.name
-            );
-            return false;
-        }
+    /// Tracks the last known tracked pane count for each stable tab ID
+    /// Used to re-pick pane count templates and re-render {pane_count}
+    stable_tab_id_to_last_pane_count: BTreeMap<u32, usize>,
 
-        #[cfg(debug_assertions)]
-        eprintln!("PLUGIN: Processing change-tab-name pipe");
+    /// Tracks the last known exit code for each stable tab ID
+    /// Used to re-render {pane_exit_code} when a command pane exits or is re-run
+    stable_tab_id_to_last_exit_code: BTreeMap<u32, Option<i32>>,
 
-        // Check for payload
-        let Some(payload) = pipe_message.payload else {
-            self.show_error("change-tab-name: missing payload");
-            return false;
-        };
+    /// Tracks the last known layout for each stable tab ID
+    /// Used to re-render {layout_marker} when panes go fullscreen or float
+    stable_tab_id_to_last_layout: BTreeMap<u32, TabLayout>,
 
-        // Parse JSON
-        let rename_payload: RenamePayload = match serde_json::from_str(&payload) {
-            Ok(p) => p,
-            Err(e) => {
-                self.show_error(&format!("change-tab-name: invalid JSON: {}", e));
-                return false;
-            }
-        };
+    /// Tracks the last known focused pane title for each stable tab ID
+    /// Used to re-render {active_pane_title} when focus moves or the title changes
+    stable_tab_id_to_last_active_pane_title: BTreeMap<u32, String>,
 
-        // Parse pane_id
-        let pane_id: u32 = match rename_payload.pane_id.parse() {
-            Ok(id) => id,
-            Err(_) => {
-                self.show_error("change-tab-name: pane_id must be a string containing a number");
-                return false;
-            }
-        };
+    /// Tracks the number of open tabs when each stable tab ID was last rendered
+    /// Used to re-render {tab_position_from_end} when tabs are opened or closed
+    stable_tab_id_to_last_tab_count: BTreeMap<u32, usize>,
 
-        // Look up tab position (current display index)
-        let Some(&tab_position) = self.pane_to_tab.get(&pane_id) else {
-            self.show_error(&format!("change-tab-name: pane {} not found in mapping (pane_to_tab has {} entries, tabs has {} entries)",
-                pane_id, self.pane_to_tab.len(), self.tabs.len()));
-            return false;
-        };
+    /// Tracks the last known project directory for each stable tab ID
+    /// Used to re-render {project_dir} when panes report new cwds or close
+    stable_tab_id_to_last_project_dir: BTreeMap<u32, String>,
 
-        // Format the tab name with tab_position placeholder
-        let final_name = match self.format_tab_name(&rename_payload.name, tab_position) {
-            Ok(name) => name,
-            Err(e) => {
-                #[cfg(debug_assertions)]
-                eprintln!(
-                    "PLUGIN: Failed to format name '{}': {}",
-                    rename_payload.name, e
-                );
+    /// Tracks the day each stable tab ID was last rendered on
+    /// Used to re-render {date} and {weekday} of live_date formats when the day changes
+    stable_tab_id_to_last_date: BTreeMap<u32, Option<chrono::NaiveDate>>,
 
-                self.show_error(&format!(
-                    "change-tab-name: invalid name format '{}': {}",
-                    rename_payload.name, e
-                ));
-                return false;
-            }
-        };
+    /// Tracks the last known short session name for each stable tab ID
+    /// Used to re-render {session_short} once the session name is known or changes
+    stable_tab_id_to_last_session_short: BTreeMap<u32, String>,
 
-        #[cfg(debug_assertions)]
-        {
-            eprintln!("\n=== PIPE RENAME REQUEST ===");
-            eprintln!(
-                "  pane_id={}, tab_position={}, final_name={:?}",
-                pane_id, tab_position, final_name
-            );
-            eprintln!("  Current pane_to_tab mappings: {:?}", self.pane_to_tab);
-            eprintln!(
-                "  Current pane_to_stable_tab_id: {:?}",
-                self.pane_to_stable_tab_id
-            );
-        }
+    /// The day a timer is set to wake the plugin at, so live_date names follow the
+    /// date. At most one such timer is pending.
+    date_rollover: Option<chrono::NaiveDate>,
 
-        // Check if rename is needed
-        if self.tabs.get(tab_position).map(|t| &t.name) == Some(&final_name) {
-            #[cfg(debug_assertions)]
-            eprintln!("PIPE: No-op, name already matches");
-            return false;
-        }
+    /// The names applied to each stable tab ID, oldest first.
+    /// Capped at MAX_HISTORY_LEN entries per tab to bound memory.
+    stable_tab_id_to_history: BTreeMap<u32, VecDeque<(String, String)>>,
 
-        // Get the tab_id to use for rename_tab
-        // See: https://github.com/zellij-org/zellij/issues/3535
-        let tab_id = if rename_payload.use_stable_ids {
-            // Mode 1 (default): Use our tracked stable tab IDs
-            // This works correctly even after tabs are deleted/reordered
-            let Some(&stable_tab_id) = self.pane_to_stable_tab_id.get(&pane_id) else {
-                self.show_error(&format!(
-                    "change-tab-name: no stable tab ID found for pane {}",
-                    pane_id
-                ));
-                return false;
-            };
+    /// When each stable tab ID was first assigned, for the {age_minutes} placeholder
+    stable_tab_id_to_created_at: BTreeMap<u32, Instant>,
 
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "PIPE: Using stable_tab_id={} (display_index={})",
-                stable_tab_id, tab_position
-            );
+    /// How many times rename_tab was called for each stable tab ID, for {rename_count}
+    stable_tab_id_to_rename_count: BTreeMap<u32, u32>,
 
-            stable_tab_id
-        } else {
-            // Mode 2: Use tab.position + 1 (1-indexed)
-            // WARNING: This breaks after tab deletion due to Zellij bug #3535
-            let Some(tab) = self.tabs.get(tab_position) else {
-                self.show_error(&format!(
-                    "change-tab-name: tab at display index {} not found",
-                    tab_position
-                ));
-                return false;
-            };
+    /// When each stable tab ID was last renamed, for rate limiting
+    stable_tab_id_to_last_rename_at: BTreeMap<u32, Instant>,
 
-            let tab_id = (tab.position as u32) + 1;
+    /// Renames deferred by rate limiting: stable tab ID -> (tab_id for rename_tab, name,
+    /// what triggered the rename). Only the latest requested name is kept per tab.
+    pending_renames: BTreeMap<u32, (u32, String, String)>,
 
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "PIPE: Using tab.position + 1 = {} (display_index={})",
-                tab_id, tab_position
-            );
+    /// Renames held back by defer_until_active, applied on the first TabUpdate
+    /// that shows the tab focused. Only the latest request is kept per tab.
+    deferred_until_active: BTreeMap<u32, RenamePayload>,
 
-            tab_id
-        };
+    /// Hash of the neighboring tab names each stored format was last rendered with
+    stable_tab_id_to_neighbor_hash: BTreeMap<u32, u64>,
 
-        #[cfg(debug_assertions)]
-        eprintln!(
-            "  >>> Calling rename_tab(tab_id={}, name={:?})",
-            tab_id, final_name
-        );
+    /// Consecutive renders triggered only by neighbor name changes
+    stable_tab_id_to_neighbor_cascade: BTreeMap<u32, u32>,
 
-        rename_tab(tab_id, final_name);
+    /// Last name the plugin applied to each tab, plus the names the tab may still
+    /// show until Zellij confirms that rename (used to spot manual renames)
+    stable_tab_id_to_applied_name: BTreeMap<u32, (String, Vec<String>)>,
 
-        // Store the original format string for auto-update on position changes
-        // (works in both modes - stable IDs are always tracked)
-        if let Some(&stable_tab_id) = self.pane_to_stable_tab_id.get(&pane_id) {
-            self.stable_tab_id_to_format_str
-                .insert(stable_tab_id, rename_payload.name.clone());
-            self.stable_tab_id_to_last_position
-                .insert(stable_tab_id, tab_position);
-
-            #[cfg(debug_assertions)]
-            eprintln!(
-                "PIPE: Stored format string {:?} for stable_tab_id {} at position {}",
-                rename_payload.name, stable_tab_id, tab_position
-            );
-        }
+    /// Name each tab had before the plugin first renamed it, restored by cleanup
+    stable_tab_id_to_previous_name: BTreeMap<u32, String>,
 
-        false
-    }
+    /// When set, render() draws the list of managed tabs and their format strings
+    overlay_enabled: bool,
 
-    fn update(&mut self, event: Event) -> bool {
-        match event {
-            Event::TabUpdate(tab_info) => {
-                self.tabs = tab_info;
-                self.rebuild_pane_to_tab();
-            }
-            Event::PaneUpdate(data) => {
-                self.panes = data;
-                self.rebuild_pane_to_tab();
-            }
-            _ => (),
-        };
+    /// The rename menu, while open. It takes precedence over the overlay.
+    menu: Option<RenameMenu>,
 
-        false
-    }
+    /// Error of a keybinding-triggered command shown above the menu or overlay
+    /// (notify_on_error), and when it was shown
+    error_banner: Option<(String, Instant)>,
 
-    fn render(&mut self, _rows: usize, _cols: usize) {}
-}
+    /// How busy and error-prone the plugin has been, for the metrics pipe
+    metrics: Metrics,
 
-impl State {
-    /// Rebuild the pane_id -> tab_position mapping from current state
-    ///
-    /// WORKAROUND for Zellij issue #3535:
-    /// Also assigns stable tab IDs to panes for use with rename_tab().
-    /// All panes in the same tab share the same stable ID, which persists
-    /// even when other tabs are deleted.
-    fn rebuild_pane_to_tab(&mut self) {
-        // Save old pane_to_tab mapping before clearing (needed for detecting pane swaps)
-        let old_pane_to_tab = self.pane_to_tab.clone();
-        self.pane_to_tab.clear();
-        // Don't clear pane_to_stable_tab_id - we want to remember stable IDs
+    /// Consecutive rebuilds in which a stable tab ID still had state stored
+    /// but belonged to no tab, for collect_stale_stable_tab_ids
+    stable_tab_id_to_missing_rebuilds: BTreeMap<u32, u32>,
 
-        #[cfg(debug_assertions)]
-        eprintln!("\n=== REBUILD PANE TO TAB ===");
+    /// The tab marked with set_as_home. Only reported to scripts via home-tab;
+    /// Zellij itself has no notion of a home tab.
+    home_stable_tab_id: Option<u32>,
 
-        #[cfg(debug_assertions)]
-        eprintln!(
-            "  INPUT: tabs.len()={}, panes.panes.len()={}",
-            self.tabs.len(),
-            self.panes.panes.len()
-        );
+    /// Stable tab IDs that auto_update_tab_names leaves alone, set per tab
+    /// with set-autoupdate-for. Renames through the pipe don't change it.
+    disabled_autoupdate: BTreeSet<u32>,
 
-        // Step 0: Build current_pane_ids and detect new panes by position
-        let mut current_pane_ids = std::collections::HashSet::new();
-        let mut new_panes_by_position: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+    /// Stable tab IDs pinned with pin-name. Auto-update and shift-renumber skip them,
+    /// while explicit renames through the pipe still apply.
+    pinned_stable_tab_ids: BTreeSet<u32>,
 
-        for (current_display_index, tab) in self.tabs.iter().enumerate() {
-            if let Some(pane_list) = self.panes.panes.get(&tab.position) {
-                for pane_info in pane_list {
-                    if !pane_info.is_plugin && !pane_info.is_suppressed {
-                        current_pane_ids.insert(pane_info.id);
+    /// For cycle-name: the list each stable tab ID last cycled through, and the
+    /// index of the name applied from it
+    stable_tab_id_to_cycle: BTreeMap<u32, (Vec<String>, usize)>,
 
-                        // Track new panes by their position
-                        if !self.pane_to_stable_tab_id.contains_key(&pane_info.id) {
-                            new_panes_by_position
-                                .entry(current_display_index)
-                                .or_default()
-                                .push(pane_info.id);
-                        }
-                    } else {
-                        #[cfg(debug_assertions)]
-                        eprintln!(
-                            "  FILTERED: pane {} (is_plugin={}, is_suppressed={})",
-                            pane_info.id, pane_info.is_plugin, pane_info.is_suppressed
-                        );
-                    }
-                }
-            }
-        }
+    /// When set, auto_update_tab_names does nothing. Format strings are still
+    /// stored so that resuming picks up any position changes made meanwhile.
+    auto_update_paused: bool,
 
-        #[cfg(debug_assertions)]
-        eprintln!("  current_pane_ids: {:?}", current_pane_ids);
+    /// Name of the session the plugin runs in, from ModeUpdate
+    session_name: Option<String>,
 
-        // Step 0.5: Transfer stable_ids from deleted panes to new panes at same position
-        // This handles the case where Zellij renumbers panes (e.g., opening scrollback editor)
-        let mut stable_id_transfers: Vec<(u32, u32, usize)> = Vec::new();
+    /// Name of the pipe message being handled, the default source of renames
+    current_pipe: Option<String>,
 
-        for (&old_pane_id, &stable_id) in &self.pane_to_stable_tab_id {
-            if !current_pane_ids.contains(&old_pane_id) {
-                // This pane is gone - check if there's a new pane at the same position
-                if let Some(&old_position) = old_pane_to_tab.get(&old_pane_id) {
-                    if let Some(new_panes) = new_panes_by_position.get_mut(&old_position) {
-                        if let Some(new_pane_id) = new_panes.pop() {
-                            stable_id_transfers.push((new_pane_id, stable_id, old_position));
-                            #[cfg(debug_assertions)]
-                            eprintln!(
-                                "  TRANSFER: pane {} -> pane {} (stable_id {} at position {})",
-                                old_pane_id, new_pane_id, stable_id, old_position
-                            );
-                        }
-                    }
-                }
-            }
+    /// Whether the user is in Zellij's RenameTab mode, i.e. typing a new name for
+    /// the active tab. Renames of that tab are deferred until the mode ends.
+    editing_active_tab_name: bool,
+
+    /// Result of the permission request, None until Zellij reports it.
+    /// rename_tab silently does nothing without ChangeApplicationState.
+    permission_status: Option<PermissionStatus>,
+
+    /// Git branch per working directory, as last reported by the git_branch worker
+    cwd_to_git_branch: BTreeMap<String, String>,
+
+    /// The last working directory reported for each pane through the pipe.
+    /// Zellij doesn't tell plugins the cwd of panes, so scripts have to.
+    pane_to_cwd: BTreeMap<u32, String>,
+
+    /// Contents of template files by path, with the modification time they were read at
+    template_file_cache: BTreeMap<String, (SystemTime, String)>,
+}
+
+register_plugin!(State);
+
+/// Background worker resolving the git branch of a working directory, so the
+/// file IO behind {git_branch} never blocks pipe handling
+#[derive(Default, Serialize, Deserialize)]
+struct GitBranchWorker {}
+
+impl ZellijWorker<'_> for GitBranchWorker {
+    fn on_message(&mut self, message: String, payload: String) {
+        if message != "resolve" {
+            return;
         }
 
-        // Collect transferred stable_ids so we don't delete them
-        let mut transferred_stable_ids = std::collections::HashSet::new();
-        for (_new_pane_id, stable_id, _position) in &stable_id_transfers {
-            transferred_stable_ids.insert(*stable_id);
+        let response = GitBranchResult {
+            branch: read_git_branch(&payload).unwrap_or_default(),
+            cwd: payload,
+        };
+        if let Ok(response) = serde_json::to_string(&response) {
+            post_message_to_plugin(PluginMessage::new_to_plugin("git_branch", &response));
         }
+    }
+}
 
-        // Apply transfers
-        for (new_pane_id, stable_id, _position) in stable_id_transfers {
-            self.pane_to_stable_tab_id.insert(new_pane_id, stable_id);
+register_worker!(GitBranchWorker, git_branch_worker, GIT_BRANCH_WORKER);
+
+/// Message sent back by the git_branch worker
+#[derive(Debug, Serialize, Deserialize)]
+struct GitBranchResult {
+    cwd: String,
+    branch: String,
+}
+
+impl ZellijPlugin for State {
+    fn load(&mut self, configuration: BTreeMap<String, String>) {
+        let (config, warnings) = Config::from_configuration(&configuration);
+        self.config = config;
+        for warning in warnings {
+            self.log(LogLevel::Warn, || warning);
         }
 
-        // Remove stable IDs for deleted panes (that weren't transferred)
-        let mut deleted_stable_ids = std::collections::HashSet::new();
-        self.pane_to_stable_tab_id.retain(|pane_id, stable_id| {
-            let exists = current_pane_ids.contains(pane_id);
-            if !exists {
-                // Don't delete stable_ids that were transferred to a new pane
-                if !transferred_stable_ids.contains(stable_id) {
-                    deleted_stable_ids.insert(*stable_id);
-                    #[cfg(debug_assertions)]
-                    eprintln!(
-                        "  CLEANUP: Removing stable_id {} for deleted pane {}",
-                        stable_id, pane_id
-                    );
+        request_permission(&[
+            PermissionType::ReadApplicationState,
+            PermissionType::ChangeApplicationState,
+        ]);
+        subscribe(&[
+            EventType::TabUpdate,
+            EventType::PaneUpdate,
+            EventType::Timer,
+            EventType::PermissionRequestResult,
+            EventType::CustomMessage,
+            EventType::ModeUpdate,
+            EventType::Key,
+        ]);
+    }
+
+    fn pipe(&mut self, pipe_message: PipeMessage) -> bool {
+        self.log(LogLevel::Debug, || {
+            format!(
+                "PLUGIN: Received pipe message: name='{}', has_payload={}",
+                pipe_message.name,
+                pipe_message.payload.is_some()
+            )
+        });
+
+        self.revalidate_mappings();
+        self.current_pipe = Some(pipe_message.name.clone());
+
+        let result = match pipe_message.name.as_str() {
+            "change-tab-name" => self.change_tab_name(pipe_message.payload.clone()),
+            "change-tab-name-b64" => decode_base64_payload(pipe_message.payload.as_deref())
+                .and_then(|payload| self.change_tab_name(Some(payload))),
+            "change-tab-name-bin" => self.change_tab_name_bin(pipe_message.payload.as_deref()),
+            "set-auto-update" => self.set_auto_update(pipe_message.payload.clone()),
+            "set-autoupdate-for" => self.set_auto_update_for(pipe_message.payload.clone()),
+            "pin-name" => self.set_pinned("pin-name", pipe_message.payload.clone(), true),
+            "unpin-name" => self.set_pinned("unpin-name", pipe_message.payload.clone(), false),
+            "history" => self.history(pipe_message.payload.clone()),
+            "set-stable-id" => self.set_stable_id(pipe_message.payload.clone()),
+            "resync-ids" => Ok(self.resync_ids()),
+            "home-tab" => Ok(self.home_tab()),
+            "describe-tab" => self.describe_tab(pipe_message.payload.clone()),
+            "reset-to-default" => self.reset_to_default(pipe_message.payload.clone()),
+            "swap-names" => self.swap_names(pipe_message.payload.clone()),
+            "stable-id-members" => self.stable_id_members(pipe_message.payload.clone()),
+            "rename-focused-tab" => self.rename_focused_tab(pipe_message.payload.clone()),
+            "rename-dynamic" => self.rename_dynamic(pipe_message.payload.clone()),
+            "toggle-overlay" => self.toggle_overlay(),
+            "rename-menu" => Ok(self.open_menu()),
+            "preview-autoupdate" => Ok(self.preview_auto_update()),
+            "metrics" => Ok(json!({ "ok": true, "metrics": self.metrics })),
+            "ping" | "version" => Ok(self.ping()),
+            "dump-state" => Ok(self.dump_state()),
+            "cleanup" => self.cleanup(),
+            "shift-renumber" => self.shift_renumber(pipe_message.payload.clone()),
+            "rename-all" => self.rename_all(pipe_message.payload.clone()),
+            "rename-by-id" => self.rename_by_id(pipe_message.payload.clone()),
+            "cycle-name" => self.cycle_name(pipe_message.payload.clone()),
+            "dump-mappings" => {
+                // Plain CSV rather than JSON, for easy parsing from shell scripts
+                self.respond_text(&pipe_message, &self.dump_mappings());
+                return self.should_render();
+            }
+            _ => {
+                self.log(LogLevel::Info, || {
+                    format!("PLUGIN: Ignoring pipe '{}'", pipe_message.name)
+                });
+                return false;
+            }
+        };
+
+        let response = match result {
+            Ok(response) => response,
+            Err(error) => {
+                self.show_error(&error.message);
+                *self.metrics.errors_by_code.entry(error.code).or_default() += 1;
+                // Nobody reads the response of a pipe sent from a keybinding
+                if self.config.notify_on_error && pipe_message.source == PipeSource::Keybind {
+                    self.notify_error(&error);
                 }
+                json!({ "ok": false, "code": error.code, "error": error.message })
             }
-            exists
+        };
+        self.respond(&pipe_message, &response);
+
+        // Re-render the overlay or menu, if shown, since commands may change what it lists
+        self.should_render()
+    }
+
+    fn update(&mut self, event: Event) -> bool {
+        match event {
+            Event::TabUpdate(tab_info) => {
+                self.tabs = tab_info;
+                self.rebuild_pane_to_tab();
+                // Renames deferred while the name was being edited go out once it's done
+                if !self.editing_active_tab_name && !self.pending_renames.is_empty() {
+                    self.flush_pending_renames();
+                }
+                self.apply_deferred_until_active();
+            }
+            Event::ModeUpdate(mode_info) => {
+                self.editing_active_tab_name = mode_info.mode == InputMode::RenameTab;
+                if mode_info.session_name.is_some() && mode_info.session_name != self.session_name {
+                    self.session_name = mode_info.session_name;
+                    // Picks up {session_short} once the name is known or after a rename
+                    self.auto_update_tab_names();
+                }
+            }
+            Event::PaneUpdate(data) => {
+                self.panes = data;
+                self.rebuild_pane_to_tab();
+            }
+            Event::Timer(_) => {
+                self.flush_pending_renames();
+                if self
+                    .date_rollover
+                    .is_some_and(|rollover| current_date().is_some_and(|today| today >= rollover))
+                {
+                    self.date_rollover = None;
+                    self.auto_update_tab_names();
+                    self.schedule_date_rollover();
+                }
+                if self
+                    .error_banner
+                    .as_ref()
+                    .is_some_and(|(_, shown_at)| shown_at.elapsed() >= ERROR_BANNER_DURATION)
+                {
+                    self.error_banner = None;
+                    self.hide_if_idle();
+                    // Repaint once more to clear the banner
+                    return true;
+                }
+            }
+            Event::PermissionRequestResult(status) => {
+                self.permission_status = Some(status);
+            }
+            Event::CustomMessage(message, payload) if message == "git_branch" => {
+                if let Ok(result) = serde_json::from_str::<GitBranchResult>(&payload) {
+                    self.update_git_branch(result);
+                }
+            }
+            Event::Key(key) if self.menu.is_some() => self.handle_menu_key(key),
+            _ => (),
+        };
+
+        self.should_render()
+    }
+
+    fn render(&mut self, rows: usize, cols: usize) {
+        let banner: Vec<String> = self
+            .error_banner
+            .iter()
+            .map(|(message, _)| format!("Error: {}", message))
+            .collect();
+        let body_rows = rows.saturating_sub(banner.len());
+        let body = if self.menu.is_some() {
+            self.menu_lines(body_rows)
+        } else if self.overlay_enabled {
+            self.overlay_lines()
+        } else if banner.is_empty() {
+            return;
+        } else {
+            Vec::new()
+        };
+        let lines = banner.into_iter().chain(body);
+
+        for line in lines.into_iter().take(rows) {
+            println!("{}", line.chars().take(cols).collect::<String>());
+        }
+    }
+}
+
+impl State {
+    /// Handle the change-tab-name pipe: rename the tab containing the given pane
+    fn change_tab_name(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        self.log(LogLevel::Debug, || {
+            "PLUGIN: Processing change-tab-name pipe".to_string()
         });
 
-        // Clean up format strings and position tracking for deleted tabs
-        for &deleted_id in &deleted_stable_ids {
-            self.stable_tab_id_to_format_str.remove(&deleted_id);
-            self.stable_tab_id_to_last_position.remove(&deleted_id);
+        let rename_payload = parse_rename_payload(payload)?;
+        if let Some(response) = self.filter_session(&rename_payload)? {
+            return Ok(response);
         }
 
-        // Step 1: Build tab_position -> stable_tab_id map from existing panes
-        let mut tab_position_to_stable_id: BTreeMap<usize, u32> = BTreeMap::new();
+        if let Some(pattern) = rename_payload.match_pattern.clone() {
+            return Ok(self.rename_matching(rename_payload, &pattern));
+        }
 
-        for (current_display_index, tab) in self.tabs.iter().enumerate() {
-            if let Some(pane_list) = self.panes.panes.get(&tab.position) {
-                for pane_info in pane_list {
-                    if !pane_info.is_plugin && !pane_info.is_suppressed {
-                        // If this pane already has a stable ID, remember it for this tab position
-                        if let Some(&stable_id) = self.pane_to_stable_tab_id.get(&pane_info.id) {
-                            tab_position_to_stable_id.insert(current_display_index, stable_id);
-                            #[cfg(debug_assertions)]
-                            eprintln!(
-                                "  EXISTING: pane {} has stable_id {} at display_index {}",
-                                pane_info.id, stable_id, current_display_index
-                            );
-                        }
-                    }
-                }
+        if let Some(needle) = rename_payload.pane_title_contains.clone() {
+            let (target, match_count) = self.resolve_pane_title(&needle)?;
+            let mut response = self.rename_with_propagation(rename_payload, target)?;
+            if match_count > 1 {
+                response["ambiguous_matches"] = json!(match_count);
             }
+            return Ok(response);
         }
 
-        // Step 2: Assign stable IDs to new panes and build pane_to_tab mapping
-        for (current_display_index, tab) in self.tabs.iter().enumerate() {
-            if let Some(pane_list) = self.panes.panes.get(&tab.position) {
-                for pane_info in pane_list {
-                    if !pane_info.is_plugin && !pane_info.is_suppressed {
-                        // Map pane to current display index
-                        self.pane_to_tab.insert(pane_info.id, current_display_index);
+        // Look up tab position (current display index) and its stable ID
+        let target = self.resolve_rename_target(&rename_payload)?;
+        self.rename_with_propagation(rename_payload, target)
+    }
 
-                        // Assign stable tab ID if this is a new pane
-                        #[allow(clippy::map_entry)]
-                        if !self.pane_to_stable_tab_id.contains_key(&pane_info.id) {
-                            let stable_id = if let Some(&existing_id) =
-                                tab_position_to_stable_id.get(&current_display_index)
-                            {
-                                // Tab already has a stable ID (from other panes), use it
-                                #[cfg(debug_assertions)]
-                                eprintln!(
-                                    "  NEW PANE in existing tab: pane {} gets stable_id {} from tab position {}",
-                                    pane_info.id, existing_id, current_display_index
-                                );
-                                existing_id
-                            } else {
-                                // New tab, assign a new stable ID
-                                // Zellij uses auto-incrementing IDs: next_id = max(current_ids) + 1
-                                // But renormalizes when lowest tab is deleted
-                                let max_stable_id = self
-                                    .pane_to_stable_tab_id
-                                    .values()
-                                    .max()
-                                    .copied()
-                                    .unwrap_or(0);
-                                let new_id = max_stable_id + 1;
-                                tab_position_to_stable_id.insert(current_display_index, new_id);
-
-                                #[cfg(debug_assertions)]
-                                {
-                                    let all_ids: Vec<_> =
-                                        self.pane_to_stable_tab_id.values().copied().collect();
-                                    eprintln!(
-                                        "  NEW TAB: pane {} assigned new stable_id {} (max was {}, existing IDs: {:?}) at position {}",
-                                        pane_info.id, new_id, max_stable_id, all_ids, current_display_index
-                                    );
-                                }
-                                new_id
-                            };
-
-                            self.pane_to_stable_tab_id.insert(pane_info.id, stable_id);
-                        }
+    /// Handle the change-tab-name-bin pipe: one or more binary (pane_id, name) frames,
+    /// each renaming the pane's tab with default options. Failing frames are reported
+    /// without stopping the others.
+    fn change_tab_name_bin(
+        &mut self,
+        payload: Option<&str>,
+    ) -> Result<serde_json::Value, PipeError> {
+        let frames = decode_binary_frames(payload.unwrap_or_default().as_bytes())?;
 
-                        #[cfg(debug_assertions)]
-                        {
-                            let stable_id = self.pane_to_stable_tab_id.get(&pane_info.id).unwrap();
-                            eprintln!(
-                                "  pane {} -> display_index={}, stable_tab_id={}",
-                                pane_info.id, current_display_index, stable_id
-                            );
-                        }
-                    }
-                }
+        // Only the defaults go through serde; the frames themselves are decoded by hand
+        let defaults: RenamePayload = parse_payload("change-tab-name-bin", Some("{}".to_string()))?;
+
+        let mut applied = 0;
+        let mut failed = Vec::new();
+        for (frame, (pane_id, name)) in frames.into_iter().enumerate() {
+            let mut rename_payload = defaults.clone();
+            rename_payload.pane_id = Some(pane_id.to_string());
+            rename_payload.name = Some(name.to_string());
+
+            let result = self
+                .resolve_rename_target(&rename_payload)
+                .and_then(|target| self.rename_target(rename_payload, target));
+            match result {
+                Ok(_) => applied += 1,
+                Err(error) => failed.push(json!({
+                    "frame": frame,
+                    "pane_id": pane_id,
+                    "code": error.code,
+                    "error": error.message,
+                })),
             }
         }
 
-        // Auto-update: Check if any tab positions have changed and re-evaluate format strings
-        self.auto_update_tab_names();
+        Ok(json!({ "ok": true, "applied": applied, "failed": failed }))
+    }
+
+    /// For a rename addressed to another session (the payload's session field),
+    /// the response that reports it as filtered out. None if the rename applies here.
+    fn filter_session(
+        &self,
+        rename_payload: &RenamePayload,
+    ) -> Result<Option<serde_json::Value>, PipeError> {
+        let Some(session) = &rename_payload.session else {
+            return Ok(None);
+        };
+        let Some(session_name) = &self.session_name else {
+            return Err(PipeError::new(
+                "NOT_READY",
+                "change-tab-name: the session name is not known yet, so the session field can't be checked. Retry in a moment",
+            ));
+        };
+        if session == session_name {
+            return Ok(None);
+        }
 
-        #[cfg(debug_assertions)]
-        eprintln!("=== END REBUILD ===\n");
+        self.log(LogLevel::Info, || {
+            format!(
+                "PIPE: Ignoring rename for session '{}' (this is '{}')",
+                session, session_name
+            )
+        });
+        Ok(Some(json!({
+            "ok": true,
+            "changed": false,
+            "filtered": true,
+            "session": session_name,
+        })))
     }
 
-    /// Auto-update tab names when positions change
-    /// For tabs with stored format strings, check if their position changed
-    /// and re-evaluate the format string with the new position
-    fn auto_update_tab_names(&mut self) {
-        // Build stable_tab_id -> current_display_index mapping
-        let mut stable_tab_id_to_current_position: BTreeMap<u32, usize> = BTreeMap::new();
-        for (&pane_id, &stable_tab_id) in &self.pane_to_stable_tab_id {
-            if let Some(&current_position) = self.pane_to_tab.get(&pane_id) {
-                stable_tab_id_to_current_position.insert(stable_tab_id, current_position);
-            }
+    /// Rename the target and, with propagate_to_same_name, every other tab that had
+    /// the same name as the target before the rename. Each tab is renamed at most once
+    /// and rendered with its own position.
+    fn rename_with_propagation(
+        &mut self,
+        rename_payload: RenamePayload,
+        target: (usize, Option<u32>),
+    ) -> Result<serde_json::Value, PipeError> {
+        if !rename_payload.propagate_to_same_name {
+            return self.rename_target(rename_payload, target);
         }
 
-        // Check each tab with a stored format string for position changes
-        let tabs_to_update: Vec<(u32, usize, String)> = self
-            .stable_tab_id_to_format_str
+        let (target_position, _) = target;
+        let old_name = self.tabs.get(target_position).map(|tab| tab.name.clone());
+        let same_name_positions: Vec<usize> = self
+            .tabs
             .iter()
-            .filter_map(|(&stable_tab_id, format_str)| {
-                let current_position = stable_tab_id_to_current_position.get(&stable_tab_id)?;
-                let last_position = self.stable_tab_id_to_last_position.get(&stable_tab_id)?;
+            .enumerate()
+            .filter(|&(tab_position, tab)| {
+                tab_position != target_position && Some(&tab.name) == old_name.as_ref()
+            })
+            .map(|(tab_position, _)| tab_position)
+            .collect();
 
-                if current_position != last_position {
-                    #[cfg(debug_assertions)]
-                    eprintln!(
-                        "AUTO-UPDATE: stable_tab_id {} moved from position {} to {}",
-                        stable_tab_id, last_position, current_position
-                    );
+        let mut response = self.rename_target(rename_payload.clone(), target)?;
 
-                    Some((stable_tab_id, *current_position, format_str.clone()))
-                } else {
-                    None
+        let mut propagated = Vec::new();
+        for tab_position in same_name_positions {
+            let target = (tab_position, self.stable_tab_id_at(tab_position));
+            let result = match self.rename_target(rename_payload.clone(), target) {
+                Ok(mut result) => {
+                    result["tab_position"] = json!(tab_position);
+                    result
                 }
+                Err(error) => json!({
+                    "ok": false,
+                    "tab_position": tab_position,
+                    "code": error.code,
+                    "error": error.message,
+                }),
+            };
+            propagated.push(result);
+        }
+        response["propagated"] = json!(propagated);
+        Ok(response)
+    }
+
+    /// Resolve the tab of the first tracked pane (by display index, then pane id)
+    /// whose title contains the given text. Also returns how many panes matched.
+    fn resolve_pane_title(&self, needle: &str) -> Result<((usize, Option<u32>), usize), PipeError> {
+        let mut matches: Vec<(usize, u32)> = self
+            .panes
+            .panes
+            .values()
+            .flatten()
+            .filter(|pane_info| !pane_info.is_plugin && pane_info.title.contains(needle))
+            .filter_map(|pane_info| {
+                let &tab_position = self.pane_to_tab.get(&pane_info.id)?;
+                Some((tab_position, pane_info.id))
             })
             .collect();
+        matches.sort();
+        matches.dedup();
 
-        // Re-evaluate and rename tabs that moved
-        for (stable_tab_id, new_position, format_str) in tabs_to_update {
-            if let Ok(new_name) = self.format_tab_name(&format_str, new_position) {
-                #[cfg(debug_assertions)]
-                eprintln!(
-                    "AUTO-UPDATE: Renaming stable_tab_id {} to {:?} (position {})",
-                    stable_tab_id, new_name, new_position
-                );
+        let Some(&(tab_position, pane_id)) = matches.first() else {
+            return Err(PipeError::new(
+                "NO_MATCHING_PANE",
+                format!("change-tab-name: no pane title contains '{}'", needle),
+            ));
+        };
+
+        let stable_tab_id = self.pane_to_stable_tab_id.get(&pane_id).copied();
+        Ok(((tab_position, stable_tab_id), matches.len()))
+    }
+
+    /// Rename every tab whose current name matches the glob pattern, with the
+    /// tab's prior name available as {old_name}. Reports one result per tab.
+    fn rename_matching(
+        &mut self,
+        rename_payload: RenamePayload,
+        pattern: &str,
+    ) -> serde_json::Value {
+        let matching_tabs: Vec<(usize, String)> = self
+            .tabs
+            .iter()
+            .enumerate()
+            .filter(|(_, tab)| glob_match(pattern, &tab.name))
+            .map(|(tab_position, tab)| (tab_position, tab.name.clone()))
+            .collect();
+
+        let mut results = Vec::new();
+        for (tab_position, old_name) in matching_tabs {
+            let mut tab_payload = rename_payload.clone();
+            tab_payload
+                .vars
+                .get_or_insert_with(BTreeMap::new)
+                .insert("old_name".to_string(), old_name.clone());
+
+            let target = (tab_position, self.stable_tab_id_at(tab_position));
+            let result = match self.rename_target(tab_payload, target) {
+                Ok(mut response) => {
+                    response["tab_position"] = json!(tab_position);
+                    response["old_name"] = json!(old_name);
+                    response
+                }
+                Err(error) => json!({
+                    "ok": false,
+                    "tab_position": tab_position,
+                    "old_name": old_name,
+                    "code": error.code,
+                    "error": error.message,
+                }),
+            };
+            results.push(result);
+        }
 
-                rename_tab(stable_tab_id, new_name);
-                self.stable_tab_id_to_last_position
-                    .insert(stable_tab_id, new_position);
+        json!({ "ok": true, "results": results })
+    }
+
+    /// Handle the rename-focused-tab pipe: rename the tab containing the focused pane,
+    /// falling back to the active tab when no focused terminal pane is found
+    fn rename_focused_tab(
+        &mut self,
+        payload: Option<String>,
+    ) -> Result<serde_json::Value, PipeError> {
+        let rename_payload = parse_rename_payload(payload)?;
+        if let Some(response) = self.filter_session(&rename_payload)? {
+            return Ok(response);
+        }
+
+        let tab_position = match self.focused_pane_tab_position() {
+            Some(tab_position) => tab_position,
+            None => self.tabs.iter().position(|t| t.active).ok_or_else(|| {
+                PipeError::new(
+                    "NO_ACTIVE_TAB",
+                    "rename-focused-tab: no focused pane or active tab",
+                )
+            })?,
+        };
+
+        let target = (tab_position, self.stable_tab_id_at(tab_position));
+        self.rename_target(rename_payload, target)
+    }
+
+    /// Handle the rename-dynamic pipe: render the name once with aggregate vars
+    /// describing all tabs, and keep the result as a fixed name
+    fn rename_dynamic(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let mut rename_payload = parse_rename_payload(payload)?;
+        if let Some(response) = self.filter_session(&rename_payload)? {
+            return Ok(response);
+        }
+
+        let vars = rename_payload.vars.get_or_insert_with(BTreeMap::new);
+        if rename_payload.strict {
+            if let Some(reserved) = vars
+                .keys()
+                .find(|name| AGGREGATE_PLACEHOLDERS.contains(&name.as_str()))
+            {
+                return Err(PipeError::new(
+                    "RESERVED_VAR",
+                    format!(
+                        "rename-dynamic: var '{}' collides with an aggregate placeholder",
+                        reserved
+                    ),
+                ));
             }
         }
+        vars.extend(self.aggregate_vars());
+
+        // The aggregate values are only correct now, so auto-update must not
+        // re-render them later
+        rename_payload.freeze_after_apply = true;
+
+        let target = self.resolve_rename_target(&rename_payload)?;
+        self.rename_target(rename_payload, target)
     }
 
-    /// Format tab name with tab_position placeholder
-    fn format_tab_name(&self, format_str: &str, tab_position: usize) -> Result<String, String> {
-        use std::collections::HashMap;
-        use strfmt::strfmt;
+    /// Values of AGGREGATE_PLACEHOLDERS for the current tabs and panes
+    fn aggregate_vars(&self) -> BTreeMap<String, String> {
+        let active_tab = self.tabs.iter().find(|tab| tab.active);
+        let tab_names: Vec<&str> = self.tabs.iter().map(|tab| tab.name.as_str()).collect();
 
-        // Create variables map with 1-indexed position
-        let mut vars = HashMap::new();
-        vars.insert("tab_position".to_string(), (tab_position + 1).to_string());
+        BTreeMap::from([
+            ("total_tabs".to_string(), self.tabs.len().to_string()),
+            (
+                "total_panes".to_string(),
+                self.pane_to_tab.len().to_string(),
+            ),
+            (
+                "active_tab_name".to_string(),
+                active_tab.map(|tab| tab.name.clone()).unwrap_or_default(),
+            ),
+            (
+                "active_tab_position".to_string(),
+                active_tab
+                    .map(|tab| (tab.position + 1).to_string())
+                    .unwrap_or_default(),
+            ),
+            ("tab_names".to_string(), tab_names.join(", ")),
+        ])
+    }
+
+    /// Display index of the tab containing the focused terminal pane.
+    ///
+    /// Zellij reports a focused pane for every tab, so the active tab's focused
+    /// pane is preferred; other tabs are only considered when the active tab has
+    /// no focused terminal pane (e.g. focus is on a plugin pane).
+    fn focused_pane_tab_position(&self) -> Option<usize> {
+        let positions: Vec<usize> = self
+            .panes
+            .panes
+            .values()
+            .flatten()
+            .filter(|p| p.is_focused && !p.is_plugin)
+            .filter_map(|p| self.pane_to_tab.get(&p.id).copied())
+            .collect();
 
-        // Let strfmt handle all validation and escaping
-        strfmt(format_str, &vars).map_err(|e| e.to_string())
+        positions
+            .iter()
+            .copied()
+            .find(|&position| self.is_tab_active(position))
+            .or_else(|| positions.iter().copied().min())
     }
 
-    /// Log an error message to stderr
-    fn show_error(&self, message: &str) {
-        eprintln!("{}", message);
+    /// Fail every pipe that renames tabs once the permission needed for rename_tab
+    /// was denied, instead of reporting renames that will never happen
+    fn check_rename_permission(&self) -> Result<(), PipeError> {
+        if self.permission_status == Some(PermissionStatus::Denied) {
+            return Err(PipeError::new(
+                "PERMISSION_DENIED",
+                "Cannot rename tab: the ChangeApplicationState permission was denied",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rename an already resolved (display index, stable tab ID) target using the payload's template
+    fn rename_target(
+        &mut self,
+        rename_payload: RenamePayload,
+        (tab_position, stable_tab_id): (usize, Option<u32>),
+    ) -> Result<serde_json::Value, PipeError> {
+        self.check_rename_permission()?;
+
+        self.record_pane_cwds(&rename_payload)?;
+
+        // The mappings are revalidated before each pipe, so this means the tab
+        // list changed while the command ran
+        if tab_position >= self.tabs.len() {
+            return Err(PipeError::new(
+                "STALE_MAPPING",
+                format!(
+                    "change-tab-name: tab at display index {} no longer exists (tabs has {} entries), retry",
+                    tab_position,
+                    self.tabs.len()
+                ),
+            ));
+        }
+
+        let format_str = self.payload_format_str(&rename_payload)?;
+        let mut tab_format = TabFormat::from_payload(
+            &rename_payload,
+            &format_str,
+            self.config.delimiters.as_ref(),
+        );
+
+        for (condition, template) in rename_payload.templates.iter().flatten() {
+            let condition = condition.parse().map_err(|_| {
+                PipeError::new(
+                    "INVALID_CONDITION",
+                    format!(
+                        "change-tab-name: invalid pane count condition '{}' in templates",
+                        condition
+                    ),
+                )
+            })?;
+            let template = match &self.config.delimiters {
+                Some((open, close)) => translate_delimiters(template, open, close),
+                None => template.clone(),
+            };
+            tab_format.templates.push((condition, template));
+        }
+
+        // Format numeric vars once, so auto-update reuses the formatted values
+        for (var, spec) in rename_payload.number_format.iter().flatten() {
+            let invalid = |message: String| {
+                PipeError::new(
+                    "INVALID_NUMBER_FORMAT",
+                    format!("change-tab-name: number_format for '{}': {}", var, message),
+                )
+            };
+            let value = tab_format
+                .vars
+                .get(var)
+                .ok_or_else(|| invalid("no var with that name".to_string()))?;
+            let formatted = format_number(value, spec).map_err(invalid)?;
+            tab_format.vars.insert(var.clone(), formatted);
+        }
+
+        if rename_payload.strict {
+            if let Some(reserved) = tab_format
+                .vars
+                .keys()
+                .find(|name| BUILTIN_PLACEHOLDERS.contains(&name.as_str()))
+            {
+                return Err(PipeError::new(
+                    "RESERVED_VAR",
+                    format!(
+                        "change-tab-name: var '{}' collides with a built-in placeholder",
+                        reserved
+                    ),
+                ));
+            }
+        }
+
+        // Rendering is linear in the template, so capping its length bounds the work
+        // a rename from an untrusted script can cause. Values are substituted in a
+        // single pass and never re-scanned, so placeholders can't expand recursively.
+        let max_length = self.config.max_template_length;
+        if max_length > 0 && tab_format.template_length() > max_length {
+            return Err(PipeError::new(
+                "TEMPLATE_TOO_LONG",
+                format!(
+                    "change-tab-name: template is {} characters long, the maximum is {}",
+                    tab_format.template_length(),
+                    max_length
+                ),
+            ));
+        }
+
+        // chrono can only report a malformed format by panicking while rendering
+        if !is_valid_date_format(&tab_format.date_format) {
+            return Err(PipeError::new(
+                "INVALID_FORMAT",
+                format!(
+                    "change-tab-name: invalid date_format '{}'",
+                    tab_format.date_format
+                ),
+            ));
+        }
+
+        // Format the tab name with tab_position placeholder
+        let final_name = self
+            .format_tab_name(&tab_format, tab_position)
+            .map_err(|e| {
+                self.log(LogLevel::Info, || {
+                    format!("PLUGIN: Failed to format name '{}': {}", format_str, e)
+                });
+
+                PipeError::new(
+                    "INVALID_FORMAT",
+                    format!(
+                        "change-tab-name: invalid name format '{}': {}",
+                        format_str, e
+                    ),
+                )
+            })?;
+
+        let final_name = if rename_payload.unique {
+            let taken: BTreeSet<&str> = self
+                .tabs
+                .iter()
+                .enumerate()
+                .filter(|&(position, _)| position != tab_position)
+                .map(|(_, tab)| tab.name.as_str())
+                .collect();
+            let is_taken =
+                |name: &str| taken.contains(self.emitted_name(&tab_format, name).as_str());
+            match (is_taken(&final_name), rename_payload.on_collision) {
+                (false, _) => final_name,
+                (true, OnCollision::Error) => {
+                    return Err(PipeError::new(
+                        "NAME_COLLISION",
+                        format!(
+                            "change-tab-name: another tab is already named '{}'",
+                            final_name
+                        ),
+                    ));
+                }
+                (true, OnCollision::Suffix) => {
+                    // max_columns may cut the suffix off, so the search is bounded
+                    let Some(suffix) = (2..self.tabs.len() + 2)
+                        .map(|n| format!(" {}", n))
+                        .find(|suffix| !is_taken(&format!("{}{}", final_name, suffix)))
+                    else {
+                        return Err(PipeError::new(
+                            "NAME_COLLISION",
+                            format!(
+                                "change-tab-name: no numeric suffix makes '{}' unique within max_columns",
+                                final_name
+                            ),
+                        ));
+                    };
+                    // Keep the number when auto-update re-renders the template
+                    tab_format.suffix.push_str(&suffix);
+                    format!("{}{}", final_name, suffix)
+                }
+            }
+        } else {
+            final_name
+        };
+
+        // The configured global prefix/suffix is only added to the name that is sent,
+        // so it's never stored with the format and a config change applies on the next render
+        let rendered_name = final_name;
+        let final_name = self.emitted_name(&tab_format, &rendered_name);
+
+        // {git_branch} renders from the cache; refresh it in the background and
+        // re-render once the worker answers
+        if let Some(cwd) = tab_format
+            .cwd
+            .as_ref()
+            .filter(|_| tab_format.uses_placeholder("git_branch"))
+        {
+            post_message_to(PluginMessage::new_to_worker("git_branch", "resolve", cwd));
+        }
+
+        self.log(LogLevel::Debug, || {
+            format!(
+                "\n=== PIPE RENAME REQUEST ===\n  pane_id={:?}, tab_position={}, final_name={:?}\n  Current pane_to_tab mappings: {:?}\n  Current pane_to_stable_tab_id: {:?}",
+                rename_payload.pane_id,
+                tab_position,
+                final_name,
+                self.pane_to_tab,
+                self.pane_to_stable_tab_id
+            )
+        });
+
+        // Get the tab_id to use for rename_tab, checking it against the caller's
+        // expectation before anything is renamed
+        let resolved_tab_id =
+            self.resolve_tab_id(rename_payload.use_stable_ids, (tab_position, stable_tab_id));
+        if let Some(expected) = rename_payload.verify_tab_id {
+            match &resolved_tab_id {
+                Ok(tab_id) if *tab_id != expected => {
+                    return Err(PipeError::new(
+                        "TAB_ID_MISMATCH",
+                        format!(
+                            "change-tab-name: resolved tab ID {} does not match verify_tab_id {}",
+                            tab_id, expected
+                        ),
+                    ));
+                }
+                Err(error) => return Err(error.clone()),
+                Ok(_) => {}
+            }
+        }
+
+        if rename_payload.set_as_home {
+            let Some(stable_tab_id) = stable_tab_id else {
+                return Err(PipeError::new(
+                    "STABLE_ID_NOT_FOUND",
+                    format!(
+                        "change-tab-name: set_as_home needs a stable tab ID, none found for tab at display index {}",
+                        tab_position
+                    ),
+                ));
+            };
+            self.home_stable_tab_id = Some(stable_tab_id);
+        }
+
+        let source = rename_payload
+            .source
+            .clone()
+            .or_else(|| self.current_pipe.clone())
+            .unwrap_or_else(|| "pipe".to_string());
+
+        if let Some(stable_tab_id) = stable_tab_id {
+            // A newer request replaces one still waiting for the tab to be focused
+            self.deferred_until_active.remove(&stable_tab_id);
+        }
+        if rename_payload.defer_until_active && !self.is_tab_active(tab_position) {
+            let Some(stable_tab_id) = stable_tab_id else {
+                return Err(PipeError::new(
+                    "STABLE_ID_NOT_FOUND",
+                    format!(
+                        "change-tab-name: defer_until_active needs a stable tab ID, none found for tab at display index {}",
+                        tab_position
+                    ),
+                ));
+            };
+            self.log(LogLevel::Info, || {
+                format!(
+                    "PIPE: Deferring rename of stable_tab_id {} until it is focused",
+                    stable_tab_id
+                )
+            });
+
+            // Rendered again when applied, so the name matches the tab at that time
+            let mut rename_payload = rename_payload;
+            rename_payload.source = Some(source.clone());
+            self.deferred_until_active
+                .insert(stable_tab_id, rename_payload);
+            return Ok(json!({
+                "ok": true,
+                "changed": false,
+                "status": "DEFERRED",
+                "stable_id": stable_tab_id,
+                "source": source,
+            }));
+        }
+
+        // Check if rename is needed
+        if self.tabs.get(tab_position).map(|t| &t.name) == Some(&final_name) {
+            self.log(LogLevel::Debug, || {
+                "PIPE: No-op, name already matches".to_string()
+            });
+            self.metrics.renames_skipped += 1;
+
+            // A deferred rename would now overwrite the name the caller asked for,
+            // and the current name now counts as set by the plugin
+            if let Some(stable_tab_id) = stable_tab_id {
+                self.pending_renames.remove(&stable_tab_id);
+                self.stable_tab_id_to_applied_name
+                    .insert(stable_tab_id, (final_name.clone(), Vec::new()));
+            }
+            return Ok(json!({
+                "ok": true,
+                "changed": false,
+                "tab_id": resolved_tab_id.ok(),
+                "mode": tab_id_mode(rename_payload.use_stable_ids),
+                "name": final_name,
+                "source": source,
+            }));
+        }
+
+        let tab_id = resolved_tab_id?;
+
+        self.log(LogLevel::Info, || {
+            format!(
+                "  >>> Calling rename_tab(tab_id={}, name={:?}, source={:?})",
+                tab_id, final_name, source
+            )
+        });
+
+        // When frozen, remember the evaluated name instead of the template
+        let tab_format = if rename_payload.freeze_after_apply {
+            TabFormat {
+                max_columns: tab_format.max_columns,
+                truncate_mode: tab_format.truncate_mode,
+                truncate_separator: tab_format.truncate_separator,
+                ..TabFormat::literal(&rendered_name)
+            }
+        } else {
+            tab_format
+        };
+
+        self.apply_rename(tab_id, stable_tab_id, final_name.clone(), &source);
+
+        // Store the original format for auto-update on position changes
+        // (works in both modes - stable IDs are always tracked)
+        if let Some(stable_tab_id) = stable_tab_id {
+            self.stable_tab_id_to_format
+                .insert(stable_tab_id, tab_format);
+            self.record_rendered_state(stable_tab_id, tab_position);
+            self.stable_tab_id_to_neighbor_cascade
+                .remove(&stable_tab_id);
+
+            self.log(LogLevel::Debug, || {
+                format!(
+                    "PIPE: Stored format string {:?} for stable_tab_id {} at position {}",
+                    format_str, stable_tab_id, tab_position
+                )
+            });
+            self.schedule_date_rollover();
+        }
+
+        Ok(json!({
+            "ok": true,
+            "changed": true,
+            "tab_id": tab_id,
+            "mode": tab_id_mode(rename_payload.use_stable_ids),
+            "name": final_name,
+            "source": source,
+        }))
+    }
+
+    /// Remember the state a tab's name was just rendered from, so auto-update only
+    /// re-renders it once its position or a placeholder's value changes
+    fn record_rendered_state(&mut self, stable_tab_id: u32, tab_position: usize) {
+        self.stable_tab_id_to_last_position
+            .insert(stable_tab_id, tab_position);
+        self.stable_tab_id_to_last_active
+            .insert(stable_tab_id, self.is_tab_active(tab_position));
+        self.stable_tab_id_to_last_sync
+            .insert(stable_tab_id, self.is_tab_syncing(tab_position));
+        self.stable_tab_id_to_last_pane_count
+            .insert(stable_tab_id, self.tab_pane_count(tab_position));
+        self.stable_tab_id_to_last_exit_code
+            .insert(stable_tab_id, self.tab_exit_code(tab_position));
+        self.stable_tab_id_to_last_layout
+            .insert(stable_tab_id, self.tab_layout(tab_position));
+        self.stable_tab_id_to_last_active_pane_title
+            .insert(stable_tab_id, self.active_pane_title(tab_position));
+        self.stable_tab_id_to_last_tab_count
+            .insert(stable_tab_id, self.tabs.len());
+        self.stable_tab_id_to_last_project_dir
+            .insert(stable_tab_id, self.project_dir(tab_position));
+        self.stable_tab_id_to_last_date
+            .insert(stable_tab_id, current_date());
+        self.stable_tab_id_to_last_session_short
+            .insert(stable_tab_id, self.session_short());
+        self.stable_tab_id_to_neighbor_hash
+            .insert(stable_tab_id, self.neighbor_names_hash(tab_position));
+    }
+
+    /// Remember the cwds reported by a rename: every entry of cwds, and cwd for the
+    /// given pane_id
+    fn record_pane_cwds(&mut self, rename_payload: &RenamePayload) -> Result<(), PipeError> {
+        for (pane_id, cwd) in rename_payload.cwds.iter().flatten() {
+            let pane_id = parse_pane_id("change-tab-name", pane_id)?;
+            self.pane_to_cwd.insert(pane_id, cwd.clone());
+        }
+
+        // Plugin pane ids live in their own namespace
+        if let (Some(pane_id), Some(cwd), false) = (
+            &rename_payload.pane_id,
+            &rename_payload.cwd,
+            rename_payload.allow_plugin_pane,
+        ) {
+            let pane_id = parse_pane_id("change-tab-name", pane_id)?;
+            self.pane_to_cwd.insert(pane_id, cwd.clone());
+        }
+        Ok(())
+    }
+
+    /// The format string of a rename: the contents of template_file if given, else name.
+    /// Template files are cached and only re-read when their modification time changes.
+    fn payload_format_str(&mut self, rename_payload: &RenamePayload) -> Result<String, PipeError> {
+        let Some(path) = &rename_payload.template_file else {
+            return rename_payload.name.clone().ok_or_else(|| {
+                PipeError::new(
+                    "INVALID_JSON",
+                    "change-tab-name: missing field `name` (or `template_file`)",
+                )
+            });
+        };
+
+        let unreadable = |e: std::io::Error| {
+            PipeError::new(
+                "TEMPLATE_FILE_UNREADABLE",
+                format!(
+                    "change-tab-name: cannot read template file '{}': {}",
+                    path, e
+                ),
+            )
+        };
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(unreadable)?;
+
+        if let Some((cached_at, contents)) = self.template_file_cache.get(path) {
+            if *cached_at == modified {
+                return Ok(contents.clone());
+            }
+        }
+
+        // A trailing newline is almost always an editor artifact, not part of the name
+        let contents = std::fs::read_to_string(path).map_err(unreadable)?;
+        let contents = contents.trim_end_matches(['\n', '\r']).to_string();
+        self.template_file_cache
+            .insert(path.clone(), (modified, contents.clone()));
+        Ok(contents)
+    }
+
+    /// The tab ID passed to rename_tab for a resolved target: the stable tab ID,
+    /// or tab.position + 1 when stable IDs are disabled
+    fn resolve_tab_id(
+        &self,
+        use_stable_ids: bool,
+        (tab_position, stable_tab_id): (usize, Option<u32>),
+    ) -> Result<u32, PipeError> {
+        // See: https://github.com/zellij-org/zellij/issues/3535
+        if use_stable_ids {
+            // Mode 1 (default): Use our tracked stable tab IDs
+            // This works correctly even after tabs are deleted/reordered
+            let Some(stable_tab_id) = stable_tab_id else {
+                return Err(PipeError::new(
+                    "STABLE_ID_NOT_FOUND",
+                    format!(
+                        "change-tab-name: no stable tab ID found for tab at display index {}",
+                        tab_position
+                    ),
+                ));
+            };
+
+            self.log(LogLevel::Debug, || {
+                format!(
+                    "PIPE: Using stable_tab_id={} (display_index={})",
+                    stable_tab_id, tab_position
+                )
+            });
+
+            Ok(stable_tab_id)
+        } else {
+            // Mode 2: Use tab.position + 1 (1-indexed)
+            // WARNING: This breaks after tab deletion due to Zellij bug #3535
+            let Some(tab) = self.tabs.get(tab_position) else {
+                return Err(PipeError::new(
+                    "TAB_NOT_FOUND",
+                    format!(
+                        "change-tab-name: tab at display index {} not found",
+                        tab_position
+                    ),
+                ));
+            };
+
+            let tab_id = (tab.position as u32) + 1;
+
+            self.log(LogLevel::Debug, || {
+                format!(
+                    "PIPE: Using tab.position + 1 = {} (display_index={})",
+                    tab_id, tab_position
+                )
+            });
+
+            Ok(tab_id)
+        }
+    }
+
+    /// Handle the history pipe: return the names applied to the tab containing the given pane
+    fn history(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let query: PaneQueryPayload = parse_payload("history", payload)?;
+        let pane_id = parse_pane_id("history", &query.pane_id)?;
+
+        let Some(&stable_tab_id) = self.pane_to_stable_tab_id.get(&pane_id) else {
+            return Err(PipeError::new(
+                "PANE_NOT_FOUND",
+                format!("history: no stable tab ID found for pane {}", pane_id),
+            ));
+        };
+
+        let (history, sources): (Vec<&String>, Vec<&String>) = self
+            .stable_tab_id_to_history
+            .get(&stable_tab_id)
+            .map(|h| h.iter().map(|(name, source)| (name, source)).unzip())
+            .unwrap_or_default();
+
+        Ok(json!({
+            "ok": true,
+            "stable_id": stable_tab_id,
+            "pinned": self.pinned_stable_tab_ids.contains(&stable_tab_id),
+            "history": history,
+            "sources": sources,
+        }))
+    }
+
+    /// Handle the set-stable-id pipe: bind the pane's tab to the given stable tab ID,
+    /// overriding the tracking heuristics. All tracked panes of the tab are updated
+    /// so they keep agreeing on the tab's ID.
+    fn set_stable_id(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let request: SetStableIdPayload = parse_payload("set-stable-id", payload)?;
+        let pane_id = parse_pane_id("set-stable-id", &request.pane_id)?;
+        let stable_id = request.stable_id;
+
+        let Some(&tab_position) = self.pane_to_tab.get(&pane_id) else {
+            return Err(PipeError::new(
+                "PANE_NOT_FOUND",
+                format!("set-stable-id: pane {} not found in mapping", pane_id),
+            ));
+        };
+
+        let bound_elsewhere = self
+            .pane_to_stable_tab_id
+            .iter()
+            .filter(|(_, &id)| id == stable_id)
+            .any(|(other_pane, _)| self.pane_to_tab.get(other_pane) != Some(&tab_position))
+            || self
+                .paneless_tab_to_stable_id
+                .iter()
+                .any(|(&position, &id)| id == stable_id && position != tab_position);
+        if bound_elsewhere {
+            return Err(PipeError::new(
+                "STABLE_ID_CONFLICT",
+                format!(
+                    "set-stable-id: stable tab ID {} is already bound to another tab",
+                    stable_id
+                ),
+            ));
+        }
+
+        let previous_stable_id = self.pane_to_stable_tab_id.get(&pane_id).copied();
+        let tab_panes: Vec<u32> = self
+            .pane_to_tab
+            .iter()
+            .filter(|(_, &position)| position == tab_position)
+            .map(|(&pane, _)| pane)
+            .collect();
+        for pane in tab_panes {
+            self.pane_to_stable_tab_id.insert(pane, stable_id);
+        }
+
+        // The replaced ID no longer names any tab
+        if let Some(previous_stable_id) = previous_stable_id.filter(|&id| id != stable_id) {
+            if !self
+                .pane_to_stable_tab_id
+                .values()
+                .any(|&id| id == previous_stable_id)
+            {
+                self.forget_stable_tab_id(previous_stable_id);
+            }
+        }
+        self.refresh_position_to_stable_tab_id();
+
+        Ok(json!({
+            "ok": true,
+            "stable_id": stable_id,
+            "previous_stable_id": previous_stable_id,
+        }))
+    }
+
+    /// Handle the describe-tab pipe: everything Zellij reports about a tab (its TabInfo
+    /// and the PaneInfo of all its panes), addressed by pane_id or tab_position
+    fn describe_tab(&self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let query: TabQueryPayload = parse_payload("describe-tab", payload)?;
+        let tab_position = self.resolve_query_tab_position("describe-tab", &query)?;
+        let Some(tab) = self.tabs.get(tab_position) else {
+            return Err(PipeError::new(
+                "TAB_NOT_FOUND",
+                format!(
+                    "describe-tab: tab at display index {} not found",
+                    tab_position
+                ),
+            ));
+        };
+        let panes = self
+            .panes
+            .panes
+            .get(&tab.position)
+            .cloned()
+            .unwrap_or_default();
+
+        let stable_tab_id = self.stable_tab_id_at(tab_position);
+        Ok(json!({
+            "ok": true,
+            "stable_id": stable_tab_id,
+            "pinned": stable_tab_id.is_some_and(|id| self.pinned_stable_tab_ids.contains(&id)),
+            "tab": tab,
+            "panes": panes,
+        }))
+    }
+
+    /// The display index of the tab a query addresses by pane_id or tab_position.
+    /// tab_position is not checked against the tab list.
+    fn resolve_query_tab_position(
+        &self,
+        command: &str,
+        query: &TabQueryPayload,
+    ) -> Result<usize, PipeError> {
+        match (&query.pane_id, query.tab_position) {
+            (Some(pane_id), _) => {
+                let pane_id = parse_pane_id(command, pane_id)?;
+                self.resolve_tab_position(pane_id, true).ok_or_else(|| {
+                    PipeError::new(
+                        "PANE_NOT_FOUND",
+                        format!("{}: pane {} not found in mapping", command, pane_id),
+                    )
+                })
+            }
+            (None, Some(tab_position)) => Ok(tab_position),
+            (None, None) => Err(PipeError::new(
+                "MISSING_TARGET",
+                format!("{}: either pane_id or tab_position is required", command),
+            )),
+        }
+    }
+
+    /// Handle the reset-to-default pipe: give the tab Zellij's default name for its
+    /// position (configurable with default_tab_name) and stop managing its name
+    fn reset_to_default(
+        &mut self,
+        payload: Option<String>,
+    ) -> Result<serde_json::Value, PipeError> {
+        let query: TabQueryPayload = parse_payload("reset-to-default", payload)?;
+        self.check_rename_permission()?;
+        let tab_position = self.resolve_query_tab_position("reset-to-default", &query)?;
+        let Some(tab) = self.tabs.get(tab_position) else {
+            return Err(PipeError::new(
+                "TAB_NOT_FOUND",
+                format!(
+                    "reset-to-default: tab at display index {} not found",
+                    tab_position
+                ),
+            ));
+        };
+
+        // Placeholders other than {tab_position} are not rendered, so the name
+        // stays what Zellij itself would pick
+        let name = self
+            .config
+            .default_tab_name
+            .replace("{tab_position}", &(tab_position + 1).to_string());
+        let changed = tab.name != name;
+
+        let stable_tab_id = self.stable_tab_id_at(tab_position);
+        let tab_id = self.resolve_tab_id(true, (tab_position, stable_tab_id))?;
+        if changed {
+            self.apply_rename(tab_id, stable_tab_id, name.clone(), "reset-to-default");
+        }
+        if let Some(stable_tab_id) = stable_tab_id {
+            self.stable_tab_id_to_format.remove(&stable_tab_id);
+        }
+
+        Ok(json!({
+            "ok": true,
+            "changed": changed,
+            "stable_id": stable_tab_id,
+            "name": name,
+        }))
+    }
+
+    /// Handle the swap-names pipe: exchange the names of two tabs along with their
+    /// stored formats, so each name is re-rendered where it now lives
+    fn swap_names(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let query: SwapNamesPayload = parse_payload("swap-names", payload)?;
+        self.check_rename_permission()?;
+        let (first_position, first_id, first_name) = self.resolve_swap_target(&query.first)?;
+        let (second_position, second_id, second_name) = self.resolve_swap_target(&query.second)?;
+
+        if first_position == second_position {
+            return Err(PipeError::new(
+                "SAME_TAB",
+                format!(
+                    "swap-names: both targets are the tab at display index {}",
+                    first_position
+                ),
+            ));
+        }
+
+        // Move both formats before rendering, so {dup_index} and {group_index}
+        // already count the other tab under its new name
+        let first_format = self.stable_tab_id_to_format.remove(&first_id);
+        let second_format = self.stable_tab_id_to_format.remove(&second_id);
+        if let Some(tab_format) = &second_format {
+            self.stable_tab_id_to_format
+                .insert(first_id, tab_format.clone());
+        }
+        if let Some(tab_format) = &first_format {
+            self.stable_tab_id_to_format
+                .insert(second_id, tab_format.clone());
+        }
+
+        let swaps = [
+            (
+                first_position,
+                first_id,
+                first_name.clone(),
+                second_format,
+                second_name.clone(),
+            ),
+            (
+                second_position,
+                second_id,
+                second_name,
+                first_format,
+                first_name,
+            ),
+        ];
+        let mut tabs = Vec::new();
+        for (tab_position, stable_tab_id, old_name, tab_format, incoming_name) in swaps {
+            // A tab without a stored format just gets the other tab's name as is
+            let name = tab_format
+                .and_then(|tab_format| self.auto_update_name(&tab_format, tab_position).ok())
+                .unwrap_or(incoming_name);
+            if name != old_name {
+                self.apply_rename(
+                    stable_tab_id,
+                    Some(stable_tab_id),
+                    name.clone(),
+                    "swap-names",
+                );
+            }
+            self.record_rendered_state(stable_tab_id, tab_position);
+            tabs.push(json!({
+                "stable_id": stable_tab_id,
+                "tab_position": tab_position,
+                "old_name": old_name,
+                "name": name,
+            }));
+        }
+        self.schedule_date_rollover();
+
+        Ok(json!({ "ok": true, "tabs": tabs }))
+    }
+
+    /// Display index, stable tab ID and current name of a swap-names target
+    fn resolve_swap_target(
+        &self,
+        tab_query: &TabQueryPayload,
+    ) -> Result<(usize, u32, String), PipeError> {
+        let tab_position = self.resolve_query_tab_position("swap-names", tab_query)?;
+        let Some(tab) = self.tabs.get(tab_position) else {
+            return Err(PipeError::new(
+                "TAB_NOT_FOUND",
+                format!(
+                    "swap-names: tab at display index {} not found",
+                    tab_position
+                ),
+            ));
+        };
+        let Some(stable_tab_id) = self.stable_tab_id_at(tab_position) else {
+            return Err(PipeError::new(
+                "STABLE_ID_NOT_FOUND",
+                format!(
+                    "swap-names: no stable tab ID found for tab at display index {}",
+                    tab_position
+                ),
+            ));
+        };
+        Ok((tab_position, stable_tab_id, tab.name.clone()))
+    }
+
+    /// Handle the stable-id-members pipe: list the panes currently mapped to a stable tab ID
+    fn stable_id_members(&self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let query: StableIdQueryPayload = parse_payload("stable-id-members", payload)?;
+        let stable_id = query.stable_id;
+
+        let pane_ids: Vec<u32> = self
+            .pane_to_stable_tab_id
+            .iter()
+            .filter(|(_, &id)| id == stable_id)
+            .map(|(&pane_id, _)| pane_id)
+            .collect();
+
+        if pane_ids.is_empty()
+            && !self
+                .paneless_tab_to_stable_id
+                .values()
+                .any(|&id| id == stable_id)
+        {
+            return Err(PipeError::new(
+                "STABLE_ID_NOT_FOUND",
+                format!(
+                    "stable-id-members: stable tab ID {} is not tracked",
+                    stable_id
+                ),
+            ));
+        }
+
+        Ok(json!({ "ok": true, "stable_id": stable_id, "pane_ids": pane_ids }))
+    }
+
+    /// Handle the dump-mappings pipe: all pane -> tab mappings as CSV with a header row,
+    /// ordered by display index then pane id
+    fn dump_mappings(&self) -> String {
+        let mut rows: Vec<(usize, u32)> = self
+            .pane_to_tab
+            .iter()
+            .map(|(&pane_id, &display_index)| (display_index, pane_id))
+            .collect();
+        rows.sort();
+
+        let mut csv = String::from("pane_id,display_index,stable_id,tab_name\n");
+        for (display_index, pane_id) in rows {
+            let stable_id = self
+                .pane_to_stable_tab_id
+                .get(&pane_id)
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            let tab_name = self.tabs.get(display_index).map_or("", |t| t.name.as_str());
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                pane_id,
+                display_index,
+                stable_id,
+                csv_field(tab_name)
+            ));
+        }
+        csv
+    }
+
+    /// Handle the dump-state pipe: the loaded configuration and every internal map,
+    /// for attaching to bug reports. Instants are given as seconds elapsed since then.
+    fn dump_state(&self) -> serde_json::Value {
+        let seconds_ago = |instants: &BTreeMap<u32, Instant>| -> BTreeMap<u32, u64> {
+            instants
+                .iter()
+                .map(|(&stable_tab_id, instant)| (stable_tab_id, instant.elapsed().as_secs()))
+                .collect()
+        };
+        let template_files: BTreeMap<&String, &String> = self
+            .template_file_cache
+            .iter()
+            .map(|(path, (_, contents))| (path, contents))
+            .collect();
+
+        // Built in parts, one json! of all of it exceeds the macro recursion limit
+        let config = json!({
+            "include_suppressed": self.config.include_suppressed,
+            "rename_interval_ms": self.config.rename_interval.as_millis() as u64,
+            "quiet": self.config.quiet,
+            "delimiters": self.config.delimiters,
+            "log_level": self.config.log_level,
+            "min_tabs_for_autoupdate": self.config.min_tabs_for_autoupdate,
+            "stale_id_gc_rebuilds": self.config.stale_id_gc_rebuilds,
+            "position_from_end_zero_based": self.config.position_from_end_zero_based,
+            "log_file": self.config.log_file,
+            "default_tab_name": self.config.default_tab_name,
+            "session_short_length": self.config.session_short_length,
+            "max_template_length": self.config.max_template_length,
+            "notify_on_error": self.config.notify_on_error,
+            "reset_ids_when_empty": self.config.reset_ids_when_empty,
+            "global_prefix": self.config.global_prefix,
+            "global_suffix": self.config.global_suffix,
+        });
+        let mappings = json!({
+            "tabs": self.tabs,
+            "panes": self.panes.panes,
+            "pane_to_tab": self.pane_to_tab,
+            "pane_to_stable_tab_id": self.pane_to_stable_tab_id,
+            "paneless_tab_to_stable_id": self.paneless_tab_to_stable_id,
+            "stable_id_high_water": self.stable_id_high_water,
+            "position_to_stable_tab_id": self.position_to_stable_tab_id,
+            "pane_to_cwd": self.pane_to_cwd,
+        });
+        let render_tracking = json!({
+            "stable_tab_id_to_format": self.stable_tab_id_to_format,
+            "stable_tab_id_to_last_position": self.stable_tab_id_to_last_position,
+            "stable_tab_id_to_last_active": self.stable_tab_id_to_last_active,
+            "stable_tab_id_to_last_sync": self.stable_tab_id_to_last_sync,
+            "stable_tab_id_to_last_pane_count": self.stable_tab_id_to_last_pane_count,
+            "stable_tab_id_to_last_exit_code": self.stable_tab_id_to_last_exit_code,
+            "stable_tab_id_to_last_layout": self.stable_tab_id_to_last_layout,
+            "stable_tab_id_to_last_active_pane_title": self.stable_tab_id_to_last_active_pane_title,
+            "stable_tab_id_to_last_tab_count": self.stable_tab_id_to_last_tab_count,
+            "stable_tab_id_to_last_project_dir": self.stable_tab_id_to_last_project_dir,
+            "stable_tab_id_to_last_date": self.stable_tab_id_to_last_date
+                .iter()
+                .map(|(id, date)| (id, date.map(|date| date.to_string())))
+                .collect::<BTreeMap<_, _>>(),
+            "stable_tab_id_to_last_session_short": self.stable_tab_id_to_last_session_short,
+            "stable_tab_id_to_neighbor_hash": self.stable_tab_id_to_neighbor_hash,
+            "stable_tab_id_to_neighbor_cascade": self.stable_tab_id_to_neighbor_cascade,
+            "stable_tab_id_to_applied_name": self.stable_tab_id_to_applied_name,
+            "stable_tab_id_to_previous_name": self.stable_tab_id_to_previous_name,
+        });
+
+        let mut state = json!({
+            "ok": true,
+            "version": env!("CARGO_PKG_VERSION"),
+            "config": config,
+            "session_name": self.session_name,
+            "permission_status": self.permission_status,
+            "overlay_enabled": self.overlay_enabled,
+            "auto_update_paused": self.auto_update_paused,
+            "disabled_autoupdate": self.disabled_autoupdate,
+            "pinned_stable_tab_ids": self.pinned_stable_tab_ids,
+            "stable_tab_id_to_cycle": self.stable_tab_id_to_cycle,
+            "deferred_until_active": self.deferred_until_active.keys().collect::<Vec<_>>(),
+            "home_stable_tab_id": self.home_stable_tab_id,
+            "stable_tab_id_to_missing_rebuilds": self.stable_tab_id_to_missing_rebuilds,
+            "date_rollover": self.date_rollover.map(|date| date.to_string()),
+            "stable_tab_id_to_history": self.stable_tab_id_to_history,
+            "stable_tab_id_to_created_secs_ago": seconds_ago(&self.stable_tab_id_to_created_at),
+            "stable_tab_id_to_rename_count": self.stable_tab_id_to_rename_count,
+            "stable_tab_id_to_last_rename_secs_ago": seconds_ago(&self.stable_tab_id_to_last_rename_at),
+            "pending_renames": self.pending_renames,
+            "cwd_to_git_branch": self.cwd_to_git_branch,
+            "template_file_cache": template_files,
+        });
+        // The parts' keys stay at the top level
+        for part in [mappings, render_tracking] {
+            if let (Some(state), serde_json::Value::Object(part)) = (state.as_object_mut(), part) {
+                state.extend(part);
+            }
+        }
+        state
+    }
+
+    /// Handle the shift-renumber pipe: rename the tab at start_position and every
+    /// tab after it with the same format, so {tab_position} numbers them in sequence.
+    /// Pinned tabs keep their names, and tabs that fail to render are reported
+    /// without stopping the others.
+    fn shift_renumber(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let request: ShiftRenumberPayload = parse_payload("shift-renumber", payload)?;
+        if request.start_position >= self.tabs.len() {
+            return Err(PipeError::new(
+                "TAB_NOT_FOUND",
+                format!(
+                    "shift-renumber: tab at display index {} not found",
+                    request.start_position
+                ),
+            ));
+        }
+
+        let rename_payload: RenamePayload = parse_payload(
+            "shift-renumber",
+            Some(json!({ "name": request.format }).to_string()),
+        )?;
+
+        let mut renamed = Vec::new();
+        let mut failed = Vec::new();
+        for tab_position in request.start_position..self.tabs.len() {
+            let target = (tab_position, self.stable_tab_id_at(tab_position));
+            if target
+                .1
+                .is_some_and(|id| self.pinned_stable_tab_ids.contains(&id))
+            {
+                continue;
+            }
+            match self.rename_target(rename_payload.clone(), target) {
+                Ok(response) if response["changed"] == json!(true) => {
+                    renamed.push(response["name"].clone());
+                }
+                Ok(_) => {}
+                Err(error) => failed.push(json!({
+                    "tab_position": tab_position,
+                    "code": error.code,
+                    "error": error.message,
+                })),
+            }
+        }
+
+        Ok(json!({ "ok": true, "renamed": renamed, "failed": failed }))
+    }
+
+    /// Handle the rename-all pipe: apply the n-th format string to the n-th tab in
+    /// display order. Tabs beyond the list keep their names. Failing entries are
+    /// reported without stopping the batch.
+    fn rename_all(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let request: RenameAllPayload = parse_payload("rename-all", payload)?;
+
+        let mut applied = 0;
+        let mut results = Vec::new();
+        for (tab_position, name) in request.names.into_iter().enumerate().take(self.tabs.len()) {
+            let result = parse_payload::<RenamePayload>(
+                "rename-all",
+                Some(json!({ "name": name }).to_string()),
+            )
+            .and_then(|rename_payload| {
+                let target = (tab_position, self.stable_tab_id_at(tab_position));
+                self.rename_target(rename_payload, target)
+            });
+
+            results.push(match result {
+                Ok(mut response) => {
+                    if response["changed"] == json!(true) {
+                        applied += 1;
+                    }
+                    response["tab_position"] = json!(tab_position);
+                    response
+                }
+                Err(error) => json!({
+                    "ok": false,
+                    "tab_position": tab_position,
+                    "code": error.code,
+                    "error": error.message,
+                }),
+            });
+        }
+
+        Ok(json!({ "ok": true, "applied": applied, "results": results }))
+    }
+
+    /// Handle the rename-by-id pipe: apply each format string to the tab with the given
+    /// stable ID, wherever it is. Failing items are reported without stopping the batch.
+    fn rename_by_id(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let request: RenameByIdPayload = parse_payload("rename-by-id", payload)?;
+
+        let mut results = Vec::new();
+        for item in request.items {
+            let stable_id = item.stable_id;
+            let result = self
+                .stable_tab_id_position(stable_id)
+                .ok_or_else(|| {
+                    PipeError::new(
+                        "STABLE_ID_NOT_FOUND",
+                        format!("rename-by-id: stable tab ID {} is not tracked", stable_id),
+                    )
+                })
+                .and_then(|tab_position| {
+                    let rename_payload: RenamePayload = parse_payload(
+                        "rename-by-id",
+                        Some(json!({ "name": item.name }).to_string()),
+                    )?;
+                    self.rename_target(rename_payload, (tab_position, Some(stable_id)))
+                });
+
+            results.push(match result {
+                Ok(mut response) => {
+                    response["stable_id"] = json!(stable_id);
+                    response
+                }
+                Err(error) => json!({
+                    "ok": false,
+                    "stable_id": stable_id,
+                    "code": error.code,
+                    "error": error.message,
+                }),
+            });
+        }
+
+        Ok(json!({ "ok": true, "results": results }))
+    }
+
+    /// Handle the cycle-name pipe: rename the tab containing the given pane to the next
+    /// name in the list, wrapping around. A different list starts over at its first name.
+    fn cycle_name(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let request: CycleNamePayload = parse_payload("cycle-name", payload)?;
+        let pane_id = parse_pane_id("cycle-name", &request.pane_id)?;
+        if request.names.is_empty() {
+            return Err(PipeError::new(
+                "INVALID_JSON",
+                "cycle-name: names must not be empty",
+            ));
+        }
+
+        let (Some(&stable_tab_id), Some(&tab_position)) = (
+            self.pane_to_stable_tab_id.get(&pane_id),
+            self.pane_to_tab.get(&pane_id),
+        ) else {
+            return Err(PipeError::new(
+                "STABLE_ID_NOT_FOUND",
+                format!("cycle-name: no stable tab ID found for pane {}", pane_id),
+            ));
+        };
+
+        let index = match self.stable_tab_id_to_cycle.get(&stable_tab_id) {
+            Some((names, index)) if *names == request.names => (index + 1) % names.len(),
+            _ => 0,
+        };
+
+        let rename_payload: RenamePayload = parse_payload(
+            "cycle-name",
+            Some(json!({ "name": request.names[index] }).to_string()),
+        )?;
+        let mut response =
+            self.rename_target(rename_payload, (tab_position, Some(stable_tab_id)))?;
+
+        self.stable_tab_id_to_cycle
+            .insert(stable_tab_id, (request.names, index));
+        response["index"] = json!(index);
+        Ok(response)
+    }
+
+    /// Handle the cleanup pipe: give every tab the plugin renamed back the name it had
+    /// before, and stop managing it. Plugins get no reliable unload hook, so this is
+    /// meant to be sent before removing the plugin.
+    fn cleanup(&mut self) -> Result<serde_json::Value, PipeError> {
+        self.check_rename_permission()?;
+
+        let mut restored = 0;
+        for (stable_tab_id, previous_name) in
+            std::mem::take(&mut self.stable_tab_id_to_previous_name)
+        {
+            self.stable_tab_id_to_format.remove(&stable_tab_id);
+            self.stable_tab_id_to_applied_name.remove(&stable_tab_id);
+            self.pending_renames.remove(&stable_tab_id);
+
+            // Only tabs that still exist can be restored
+            if self.stable_tab_id_position(stable_tab_id).is_some() {
+                self.audit_rename(Some(stable_tab_id), None, &previous_name, "cleanup");
+                rename_tab(stable_tab_id, &previous_name);
+                self.record_history(stable_tab_id, &previous_name, "cleanup");
+                self.metrics.renames_applied += 1;
+                restored += 1;
+            }
+        }
+
+        Ok(json!({ "ok": true, "restored": restored }))
+    }
+
+    /// Handle the ping/version pipe: a handshake telling scripts which plugin version
+    /// is running and what it currently tracks
+    fn ping(&self) -> serde_json::Value {
+        let permissions = match self.permission_status {
+            Some(PermissionStatus::Granted) => "granted",
+            Some(PermissionStatus::Denied) => "denied",
+            None => "pending",
+        };
+
+        json!({
+            "ok": true,
+            "version": env!("CARGO_PKG_VERSION"),
+            "permissions": permissions,
+            "tracked_panes": self.pane_to_tab.len(),
+            "tracked_tabs": self.tabs.len(),
+        })
+    }
+
+    /// Whether render() draws anything, so events affecting it should re-render
+    fn should_render(&self) -> bool {
+        self.overlay_enabled || self.menu.is_some() || self.error_banner.is_some()
+    }
+
+    /// Show a failed command's error in the plugin's pane for ERROR_BANNER_DURATION,
+    /// bringing the pane into view. zellij-tile has no notification API, so this
+    /// is the closest to a transient banner a plugin can show.
+    fn notify_error(&mut self, error: &PipeError) {
+        self.error_banner = Some((
+            format!("{} ({})", error.message, error.code),
+            Instant::now(),
+        ));
+        show_self(true);
+        set_timeout(ERROR_BANNER_DURATION.as_secs_f64());
+    }
+
+    /// Hide the plugin's pane again once render() has nothing left to draw.
+    /// The overlay is never brought into view by the plugin, but keeps the pane
+    /// visible while it is enabled.
+    fn hide_if_idle(&self) {
+        if !self.should_render() {
+            hide_self();
+        }
+    }
+
+    /// Handle the rename-menu pipe: open the rename menu on the active tab and bring
+    /// the plugin's pane into view, so the user can pick a tab and type its new name
+    fn open_menu(&mut self) -> serde_json::Value {
+        self.menu = Some(RenameMenu {
+            selected: self.tabs.iter().position(|t| t.active).unwrap_or(0),
+            ..RenameMenu::default()
+        });
+        show_self(true);
+        json!({ "ok": true })
+    }
+
+    /// Move through the rename menu: arrows or j/k pick a tab, Enter or its number
+    /// starts typing a name, Enter applies it and Esc goes back or closes the menu
+    fn handle_menu_key(&mut self, key: Key) {
+        let tab_count = self.tabs.len();
+        let Some(menu) = self.menu.as_mut() else {
+            return;
+        };
+        menu.selected = menu.selected.min(tab_count.saturating_sub(1));
+
+        let Some(input) = menu.input.as_mut() else {
+            match key {
+                Key::Up | Key::Char('k') => menu.selected = menu.selected.saturating_sub(1),
+                Key::Down | Key::Char('j') if menu.selected + 1 < tab_count => menu.selected += 1,
+                Key::Char('\n') if tab_count > 0 => menu.input = Some(String::new()),
+                Key::Char(c @ '1'..='9') => {
+                    let index = c as usize - '1' as usize;
+                    if index < tab_count {
+                        menu.selected = index;
+                        menu.input = Some(String::new());
+                    }
+                }
+                Key::Esc | Key::Char('q') => {
+                    self.menu = None;
+                    self.hide_if_idle();
+                }
+                _ => {}
+            }
+            return;
+        };
+
+        match key {
+            Key::Char('\n') => {
+                let name = input.clone();
+                let tab_position = menu.selected;
+                self.rename_from_menu(tab_position, name);
+            }
+            Key::Char(c) if !c.is_control() => input.push(c),
+            Key::Backspace => {
+                input.pop();
+            }
+            Key::Esc => {
+                menu.input = None;
+                menu.error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply the name typed in the menu like a change-tab-name rename through the
+    /// tab's stable ID. The menu closes on success and shows the error otherwise.
+    fn rename_from_menu(&mut self, tab_position: usize, name: String) {
+        let result = parse_payload::<RenamePayload>(
+            "rename-menu",
+            Some(json!({ "name": name, "source": "rename-menu" }).to_string()),
+        )
+        .and_then(|rename_payload| {
+            let target = (tab_position, self.stable_tab_id_at(tab_position));
+            self.rename_target(rename_payload, target)
+        });
+
+        match result {
+            Ok(_) => {
+                self.menu = None;
+                self.hide_if_idle();
+            }
+            Err(error) => {
+                self.show_error(&error.message);
+                if let Some(menu) = self.menu.as_mut() {
+                    menu.error = Some(error.message);
+                }
+            }
+        }
+    }
+
+    /// Lines shown by render() while the rename menu is open, scrolled so the
+    /// highlighted tab fits in the given number of rows
+    fn menu_lines(&self, rows: usize) -> Vec<String> {
+        let Some(menu) = &self.menu else {
+            return Vec::new();
+        };
+        let selected = menu.selected.min(self.tabs.len().saturating_sub(1));
+
+        let mut lines = Vec::new();
+        match &menu.input {
+            Some(input) => {
+                let old_name = self.tabs.get(selected).map_or("", |t| t.name.as_str());
+                lines.push(format!(
+                    "Rename tab {} ({}): Enter to apply, Esc to go back",
+                    selected + 1,
+                    old_name
+                ));
+                lines.push(format!("> {}_", input));
+            }
+            None => {
+                lines.push(
+                    "Rename tab: Up/Down or number to pick, Enter to rename, Esc to close"
+                        .to_string(),
+                );
+                let visible = rows.saturating_sub(lines.len() + 1).max(1);
+                let first = (selected + 1).saturating_sub(visible);
+                for (position, tab) in self.tabs.iter().enumerate().skip(first).take(visible) {
+                    let marker = if position == selected { '>' } else { ' ' };
+                    lines.push(format!("{} {:>3} {}", marker, position + 1, tab.name));
+                }
+            }
+        }
+        if let Some(error) = &menu.error {
+            lines.push(format!("Error: {}", error));
+        }
+        lines
+    }
+
+    /// Handle the toggle-overlay pipe: show or hide the managed tab list in render()
+    fn toggle_overlay(&mut self) -> Result<serde_json::Value, PipeError> {
+        self.overlay_enabled = !self.overlay_enabled;
+        Ok(json!({ "ok": true, "enabled": self.overlay_enabled }))
+    }
+
+    /// Lines shown by render() in overlay mode: one per managed tab, in display order
+    fn overlay_lines(&self) -> Vec<String> {
+        let mut managed: Vec<(usize, u32, &TabFormat)> = self
+            .stable_tab_id_to_format
+            .iter()
+            .filter_map(|(&stable_tab_id, tab_format)| {
+                let position = (0..self.tabs.len())
+                    .find(|&position| self.stable_tab_id_at(position) == Some(stable_tab_id))?;
+                Some((position, stable_tab_id, tab_format))
+            })
+            .collect();
+        managed.sort_by_key(|&(position, _, _)| position);
+
+        let mut lines = vec![format!("zellij-tab-name: {} managed tab(s)", managed.len())];
+        for (position, stable_tab_id, tab_format) in managed {
+            let name = self.tabs.get(position).map_or("", |t| t.name.as_str());
+            lines.push(format!(
+                "{:>3} [id {}] {}  <- {}{}{}",
+                position + 1,
+                stable_tab_id,
+                name,
+                tab_format.prefix,
+                tab_format.format_str,
+                tab_format.suffix
+            ));
+        }
+        lines
+    }
+
+    /// Handle the set-auto-update pipe: pause or resume auto-update globally
+    fn set_auto_update(&mut self, payload: Option<String>) -> Result<serde_json::Value, PipeError> {
+        let auto_update_payload: SetAutoUpdatePayload = parse_payload("set-auto-update", payload)?;
+
+        self.auto_update_paused = !auto_update_payload.enabled;
+
+        self.log(LogLevel::Debug, || {
+            format!(
+                "PIPE: Auto-update {}",
+                if auto_update_payload.enabled {
+                    "enabled"
+                } else {
+                    "paused"
+                }
+            )
+        });
+
+        // Catch up on any position changes that happened while paused
+        if auto_update_payload.enabled {
+            self.auto_update_tab_names();
+        }
+
+        Ok(json!({ "ok": true, "enabled": auto_update_payload.enabled }))
+    }
+
+    /// Handle the set-autoupdate-for pipe: turn auto-update off or back on for the
+    /// tab containing the given pane, leaving other tabs alone
+    fn set_auto_update_for(
+        &mut self,
+        payload: Option<String>,
+    ) -> Result<serde_json::Value, PipeError> {
+        let request: SetAutoUpdateForPayload = parse_payload("set-autoupdate-for", payload)?;
+        let pane_id = parse_pane_id("set-autoupdate-for", &request.pane_id)?;
+
+        let Some(&stable_tab_id) = self.pane_to_stable_tab_id.get(&pane_id) else {
+            return Err(PipeError::new(
+                "STABLE_ID_NOT_FOUND",
+                format!(
+                    "set-autoupdate-for: no stable tab ID found for pane {}",
+                    pane_id
+                ),
+            ));
+        };
+
+        if request.enabled {
+            self.disabled_autoupdate.remove(&stable_tab_id);
+            // Catch up on any changes made while it was disabled
+            self.auto_update_tab_names();
+        } else {
+            self.disabled_autoupdate.insert(stable_tab_id);
+        }
+
+        self.log(LogLevel::Debug, || {
+            format!(
+                "PIPE: Auto-update {} for stable_tab_id {}",
+                if request.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                stable_tab_id
+            )
+        });
+
+        Ok(json!({
+            "ok": true,
+            "stable_id": stable_tab_id,
+            "enabled": request.enabled,
+        }))
+    }
+
+    /// Handle the pin-name and unpin-name pipes: pin the tab containing the given pane,
+    /// so auto-update and shift-renumber leave its name alone, or unpin it again
+    fn set_pinned(
+        &mut self,
+        command: &str,
+        payload: Option<String>,
+        pinned: bool,
+    ) -> Result<serde_json::Value, PipeError> {
+        let query: PaneQueryPayload = parse_payload(command, payload)?;
+        let pane_id = parse_pane_id(command, &query.pane_id)?;
+
+        let Some(&stable_tab_id) = self.pane_to_stable_tab_id.get(&pane_id) else {
+            return Err(PipeError::new(
+                "STABLE_ID_NOT_FOUND",
+                format!("{}: no stable tab ID found for pane {}", command, pane_id),
+            ));
+        };
+
+        if pinned {
+            self.pinned_stable_tab_ids.insert(stable_tab_id);
+        } else {
+            self.pinned_stable_tab_ids.remove(&stable_tab_id);
+            // Catch up on any changes made while it was pinned
+            self.auto_update_tab_names();
+        }
+
+        self.log(LogLevel::Debug, || {
+            format!(
+                "PIPE: stable_tab_id {} {}",
+                stable_tab_id,
+                if pinned { "pinned" } else { "unpinned" }
+            )
+        });
+
+        Ok(json!({ "ok": true, "stable_id": stable_tab_id, "pinned": pinned }))
+    }
+
+    /// Rebuild the pane_id -> tab_position mapping from current state
+    ///
+    /// WORKAROUND for Zellij issue #3535:
+    /// Also assigns stable tab IDs to panes for use with rename_tab().
+    /// All panes in the same tab share the same stable ID, which persists
+    /// even when other tabs are deleted.
+    fn rebuild_pane_to_tab(&mut self) {
+        let include_suppressed = self.config.include_suppressed;
+
+        // Take the old pane_to_tab mapping instead of cloning it (needed for detecting
+        // pane swaps); it is rebuilt from scratch below
+        let old_pane_to_tab = std::mem::take(&mut self.pane_to_tab);
+        // Don't clear pane_to_stable_tab_id - we want to remember stable IDs
+
+        self.log(LogLevel::Debug, || {
+            "\n=== REBUILD PANE TO TAB ===".to_string()
+        });
+
+        self.log(LogLevel::Debug, || {
+            format!(
+                "  INPUT: tabs.len()={}, panes.panes.len()={}",
+                self.tabs.len(),
+                self.panes.panes.len()
+            )
+        });
+
+        // Zellij can briefly report tabs out of display order. Every display index
+        // below is an index into self.tabs, so order it by TabInfo.position first
+        // instead of renaming the wrong tabs for one update.
+        if !self
+            .tabs
+            .windows(2)
+            .all(|pair| pair[0].position <= pair[1].position)
+        {
+            self.log(LogLevel::Debug, || {
+                "  Tabs reported out of order, sorting by position".to_string()
+            });
+            self.tabs.sort_by_key(|tab| tab.position);
+        }
+
+        // Step 0: Build current_pane_ids and detect new panes by position.
+        // This is the only pass over the manifest: later steps walk tracked_panes,
+        // the (display index, pane id) pairs in manifest order.
+        let mut current_pane_ids = std::collections::HashSet::new();
+        let mut new_panes_by_position: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+        let mut tracked_panes: Vec<(usize, u32)> = Vec::new();
+
+        // Zellij may transiently report the same pane in more than one tab.
+        // Keep the first (leftmost) tab's entry and ignore the rest so the
+        // mapping stays deterministic.
+        let mut pane_first_display_index: BTreeMap<u32, usize> = BTreeMap::new();
+
+        for (current_display_index, tab) in self.tabs.iter().enumerate() {
+            if let Some(pane_list) = self.panes.panes.get(&tab.position) {
+                for pane_info in pane_list {
+                    if is_tracked_pane(pane_info, include_suppressed) {
+                        if let Some(&first_index) = pane_first_display_index.get(&pane_info.id) {
+                            if first_index != current_display_index {
+                                self.log(LogLevel::Warn, || format!(
+                                    "pane {} reported in tabs at display index {} and {}, keeping {}",
+                                    pane_info.id, first_index, current_display_index, first_index
+                                ));
+                            }
+                            continue;
+                        }
+                        pane_first_display_index.insert(pane_info.id, current_display_index);
+                        current_pane_ids.insert(pane_info.id);
+                        tracked_panes.push((current_display_index, pane_info.id));
+
+                        // Track new panes by their position
+                        if !self.pane_to_stable_tab_id.contains_key(&pane_info.id) {
+                            new_panes_by_position
+                                .entry(current_display_index)
+                                .or_default()
+                                .push(pane_info.id);
+                        }
+                    } else {
+                        self.log(LogLevel::Debug, || {
+                            format!(
+                                "  FILTERED: pane {} (is_plugin={}, is_suppressed={})",
+                                pane_info.id, pane_info.is_plugin, pane_info.is_suppressed
+                            )
+                        });
+                    }
+                }
+            }
+        }
+
+        self.log(LogLevel::Debug, || {
+            format!("  current_pane_ids: {:?}", current_pane_ids)
+        });
+
+        // Step 0.5: Transfer stable_ids from deleted panes to new panes at same position
+        // This handles the case where Zellij renumbers panes (e.g., opening scrollback editor)
+        //
+        // Positions are ambiguous when many tabs move at once (e.g. move-tab), so
+        // prefer pane identity: stable IDs that still have surviving panes keep
+        // their tab, and a position already occupied by surviving panes of
+        // another tab never receives a transfer.
+        let mut stable_id_transfers: Vec<(u32, u32, usize)> = Vec::new();
+
+        let mut surviving_stable_ids = std::collections::HashSet::new();
+        let mut occupied_positions = std::collections::HashSet::new();
+        for (pane_id, &position) in &pane_first_display_index {
+            if let Some(&stable_id) = self.pane_to_stable_tab_id.get(pane_id) {
+                surviving_stable_ids.insert(stable_id);
+                occupied_positions.insert(position);
+            }
+        }
+
+        for (&old_pane_id, &stable_id) in &self.pane_to_stable_tab_id {
+            if !current_pane_ids.contains(&old_pane_id)
+                && !surviving_stable_ids.contains(&stable_id)
+            {
+                // This pane is gone - check if there's a new pane at the same position
+                if let Some(&old_position) = old_pane_to_tab.get(&old_pane_id) {
+                    if occupied_positions.contains(&old_position) {
+                        self.log(LogLevel::Debug, || {
+                            format!(
+                            "  NO TRANSFER: stable_id {} (position {} now belongs to another tab)",
+                            stable_id, old_position
+                        )
+                        });
+                        continue;
+                    }
+                    if let Some(new_panes) = new_panes_by_position.get_mut(&old_position) {
+                        if let Some(new_pane_id) = new_panes.pop() {
+                            stable_id_transfers.push((new_pane_id, stable_id, old_position));
+                            self.log(LogLevel::Debug, || {
+                                format!(
+                                    "  TRANSFER: pane {} -> pane {} (stable_id {} at position {})",
+                                    old_pane_id, new_pane_id, stable_id, old_position
+                                )
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Collect transferred stable_ids so we don't delete them
+        let mut transferred_stable_ids = std::collections::HashSet::new();
+        for (_new_pane_id, stable_id, _position) in &stable_id_transfers {
+            transferred_stable_ids.insert(*stable_id);
+        }
+
+        // Apply transfers
+        for (new_pane_id, stable_id, _position) in stable_id_transfers {
+            self.pane_to_stable_tab_id.insert(new_pane_id, stable_id);
+        }
+
+        // Remove stable IDs for deleted panes (that weren't transferred)
+        let mut deleted_stable_ids = std::collections::HashSet::new();
+        let mut removed_panes = Vec::new();
+        self.pane_to_stable_tab_id.retain(|pane_id, stable_id| {
+            let exists = current_pane_ids.contains(pane_id);
+            if !exists {
+                // Don't delete stable_ids that were transferred to a new pane
+                if !transferred_stable_ids.contains(stable_id) {
+                    deleted_stable_ids.insert(*stable_id);
+                    removed_panes.push((*pane_id, *stable_id));
+                }
+            }
+            exists
+        });
+        self.pane_to_cwd
+            .retain(|pane_id, _| current_pane_ids.contains(pane_id));
+        for (pane_id, stable_id) in removed_panes {
+            self.log(LogLevel::Debug, || {
+                format!(
+                    "  CLEANUP: Removing stable_id {} for deleted pane {}",
+                    stable_id, pane_id
+                )
+            });
+        }
+
+        // A stable ID is only deleted once none of its tab's panes remain
+        let remaining_stable_ids: std::collections::HashSet<u32> =
+            self.pane_to_stable_tab_id.values().copied().collect();
+        deleted_stable_ids.retain(|stable_id| !remaining_stable_ids.contains(stable_id));
+
+        // Clean up format strings and position tracking for deleted tabs
+        for &deleted_id in &deleted_stable_ids {
+            self.forget_stable_tab_id(deleted_id);
+        }
+
+        // Step 1: Build tab_position -> stable_tab_id map from existing panes.
+        // If panes of several stable IDs share a position (ambiguous after bulk
+        // moves), the ID held by most panes wins, lowest ID on ties.
+        let mut position_stable_id_counts: BTreeMap<usize, BTreeMap<u32, usize>> = BTreeMap::new();
+
+        for &(current_display_index, pane_id) in &tracked_panes {
+            // If this pane already has a stable ID, remember it for this tab position
+            if let Some(&stable_id) = self.pane_to_stable_tab_id.get(&pane_id) {
+                *position_stable_id_counts
+                    .entry(current_display_index)
+                    .or_default()
+                    .entry(stable_id)
+                    .or_default() += 1;
+                self.log(LogLevel::Debug, || {
+                    format!(
+                        "  EXISTING: pane {} has stable_id {} at display_index {}",
+                        pane_id, stable_id, current_display_index
+                    )
+                });
+            }
+        }
+
+        let mut tab_position_to_stable_id: BTreeMap<usize, u32> = position_stable_id_counts
+            .into_iter()
+            .filter_map(|(position, counts)| {
+                let (&stable_id, _) = counts
+                    .iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))?;
+                Some((position, stable_id))
+            })
+            .collect();
+
+        // Step 2: Assign stable IDs to new panes and to tabs with no tracked panes
+        // (e.g. a tab whose only pane is a plugin), left to right, and build the
+        // pane_to_tab mapping.
+        //
+        // Pane-less tabs have no pane to carry their identity between rebuilds, so
+        // the n-th pane-less tab keeps the n-th pane-less stable ID from the
+        // previous rebuild. A pane-less tab that gains a tracked pane keeps its ID
+        // (and format) rather than being treated as a new tab.
+        let tracked_positions: BTreeSet<usize> = tracked_panes
+            .iter()
+            .map(|&(current_display_index, _)| current_display_index)
+            .collect();
+        let previous_paneless_ids = std::mem::take(&mut self.paneless_tab_to_stable_id);
+        // New IDs stay above the previous pane-less IDs, which may still be reused
+        let paneless_id_floor = previous_paneless_ids.values().copied().max().unwrap_or(0);
+        let mut unused_paneless_ids = VecDeque::new();
+        for (position, stable_id) in previous_paneless_ids {
+            if tracked_positions.contains(&position)
+                && !tab_position_to_stable_id.contains_key(&position)
+            {
+                self.log(LogLevel::Debug, || {
+                    format!(
+                        "  PANELESS TAB GAINED PANES: display_index={} keeps stable_tab_id={}",
+                        position, stable_id
+                    )
+                });
+                tab_position_to_stable_id.insert(position, stable_id);
+            } else {
+                unused_paneless_ids.push_back(stable_id);
+            }
+        }
+
+        let mut remaining_panes = tracked_panes.iter().copied().peekable();
+        for current_display_index in 0..self.tabs.len() {
+            if !tracked_positions.contains(&current_display_index) {
+                let stable_id = match unused_paneless_ids.pop_front() {
+                    Some(stable_id) => stable_id,
+                    None => {
+                        let new_id = self.next_stable_tab_id(paneless_id_floor);
+                        self.stable_tab_id_to_created_at
+                            .insert(new_id, Instant::now());
+                        new_id
+                    }
+                };
+
+                self.log(LogLevel::Debug, || {
+                    format!(
+                        "  PANELESS TAB: display_index={} -> stable_tab_id={}",
+                        current_display_index, stable_id
+                    )
+                });
+
+                self.paneless_tab_to_stable_id
+                    .insert(current_display_index, stable_id);
+                continue;
+            }
+
+            while let Some((_, pane_id)) =
+                remaining_panes.next_if(|&(index, _)| index == current_display_index)
+            {
+                // Map pane to current display index
+                self.pane_to_tab.insert(pane_id, current_display_index);
+
+                // Assign stable tab ID if this is a new pane
+                #[allow(clippy::map_entry)]
+                if !self.pane_to_stable_tab_id.contains_key(&pane_id) {
+                    let stable_id = if let Some(&existing_id) =
+                        tab_position_to_stable_id.get(&current_display_index)
+                    {
+                        // Tab already has a stable ID (from other panes), use it
+                        self.log(LogLevel::Debug, || format!(
+                            "  NEW PANE in existing tab: pane {} gets stable_id {} from tab position {}",
+                            pane_id, existing_id, current_display_index
+                        ));
+                        existing_id
+                    } else {
+                        // New tab, assign a new stable ID
+                        // Zellij uses auto-incrementing IDs: next_id = max(current_ids) + 1
+                        // But renormalizes when lowest tab is deleted
+                        let new_id = self.next_stable_tab_id(paneless_id_floor);
+                        tab_position_to_stable_id.insert(current_display_index, new_id);
+                        self.stable_tab_id_to_created_at
+                            .insert(new_id, Instant::now());
+
+                        self.log(LogLevel::Debug, || {
+                            let all_ids: Vec<_> =
+                                self.pane_to_stable_tab_id.values().copied().collect();
+                            format!(
+                                "  NEW TAB: pane {} assigned new stable_id {} (existing IDs: {:?}) at position {}",
+                                pane_id, new_id, all_ids, current_display_index
+                            )
+                        });
+                        new_id
+                    };
+
+                    self.pane_to_stable_tab_id.insert(pane_id, stable_id);
+                }
+
+                self.log(LogLevel::Debug, || {
+                    format!(
+                        "  pane {} -> display_index={}, stable_tab_id={:?}",
+                        pane_id,
+                        current_display_index,
+                        self.pane_to_stable_tab_id.get(&pane_id)
+                    )
+                });
+            }
+        }
+
+        // Pane-less tabs that went away
+        for stable_id in unused_paneless_ids {
+            self.forget_stable_tab_id(stable_id);
+        }
+
+        // Step 2.5: Restore the one stable ID per tab invariant if a transfer broke it
+        self.split_duplicate_stable_ids();
+
+        // Reclaim state of stable IDs that cleanup above missed
+        self.collect_stale_stable_tab_ids();
+
+        // Pane-less IDs don't count: tabs whose panes are reported closed before
+        // the tabs themselves briefly get fresh pane-less IDs
+        if let Some(&max_stable_id) = self.pane_to_stable_tab_id.values().max() {
+            self.stable_id_high_water = self.stable_id_high_water.max(max_stable_id);
+        }
+
+        // Everything after this looks tabs' stable IDs up by position
+        self.refresh_position_to_stable_tab_id();
+
+        // Before auto-update re-renders anything, give up on tabs the user renamed
+        self.yield_externally_renamed_tabs();
+
+        // Auto-update: Check if any tab positions have changed and re-evaluate format strings
+        self.auto_update_tab_names();
+
+        self.log(LogLevel::Debug, || "=== END REBUILD ===\n".to_string());
+    }
+
+    /// Rebuild the mappings if any of them points past the last tab. Every
+    /// TabUpdate rebuilds them, so this only matters if an update was missed or
+    /// handled out of order.
+    fn revalidate_mappings(&mut self) {
+        let tab_count = self.tabs.len();
+        let stale = self
+            .pane_to_tab
+            .values()
+            .chain(self.paneless_tab_to_stable_id.keys())
+            .any(|&tab_position| tab_position >= tab_count);
+        if stale {
+            self.log(LogLevel::Info, || {
+                format!(
+                    "PLUGIN: Mappings point past the last of {} tabs, rebuilding",
+                    tab_count
+                )
+            });
+            self.rebuild_pane_to_tab();
+        }
+    }
+
+    /// Give every tab its own stable ID again when one ID is mapped to panes in more
+    /// than one tab, which would make auto-update rename those tabs in lockstep.
+    /// The tab with the most panes carrying the ID (the leftmost on a tie) keeps it and
+    /// its stored state; the others get a fresh ID.
+    fn split_duplicate_stable_ids(&mut self) {
+        let mut id_to_position_counts: BTreeMap<u32, BTreeMap<usize, usize>> = BTreeMap::new();
+        for (pane_id, &stable_id) in &self.pane_to_stable_tab_id {
+            if let Some(&position) = self.pane_to_tab.get(pane_id) {
+                *id_to_position_counts
+                    .entry(stable_id)
+                    .or_default()
+                    .entry(position)
+                    .or_default() += 1;
+            }
+        }
+
+        for (stable_id, position_counts) in id_to_position_counts {
+            if position_counts.len() < 2 {
+                continue;
+            }
+            let Some((&kept_position, _)) = position_counts
+                .iter()
+                .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            else {
+                continue;
+            };
+
+            for &position in position_counts.keys().filter(|&&p| p != kept_position) {
+                let new_id = self.next_stable_tab_id(0);
+                let panes: Vec<u32> = self
+                    .pane_to_stable_tab_id
+                    .iter()
+                    .filter(|(pane_id, &id)| {
+                        id == stable_id && self.pane_to_tab.get(pane_id) == Some(&position)
+                    })
+                    .map(|(&pane_id, _)| pane_id)
+                    .collect();
+                for &pane_id in &panes {
+                    self.pane_to_stable_tab_id.insert(pane_id, new_id);
+                }
+                self.stable_tab_id_to_created_at
+                    .insert(new_id, Instant::now());
+
+                self.log(LogLevel::Warn, || format!(
+                    "stable_id {} was shared by tabs at display index {} and {}, moved panes {:?} to new stable_id {}",
+                    stable_id, kept_position, position, panes, new_id
+                ));
+            }
+        }
+    }
+
+    /// Drop the state of stable tab IDs that have belonged to no tab for more than
+    /// stale_id_gc_rebuilds rebuilds. Closed tabs are normally forgotten right away;
+    /// this catches state left behind by edge cases (e.g. IDs replaced by hand).
+    fn collect_stale_stable_tab_ids(&mut self) {
+        let threshold = self.config.stale_id_gc_rebuilds;
+        if threshold == 0 {
+            return;
+        }
+
+        let live_ids: BTreeSet<u32> = self
+            .pane_to_stable_tab_id
+            .values()
+            .chain(self.paneless_tab_to_stable_id.values())
+            .copied()
+            .collect();
+        let stored_ids: BTreeSet<u32> = self
+            .stable_tab_id_to_format
+            .keys()
+            .chain(self.stable_tab_id_to_last_position.keys())
+            .chain(self.stable_tab_id_to_history.keys())
+            .chain(self.stable_tab_id_to_created_at.keys())
+            .chain(self.stable_tab_id_to_last_rename_at.keys())
+            .chain(self.stable_tab_id_to_applied_name.keys())
+            .chain(self.stable_tab_id_to_previous_name.keys())
+            .chain(self.stable_tab_id_to_rename_count.keys())
+            .chain(self.pending_renames.keys())
+            .chain(self.disabled_autoupdate.iter())
+            .chain(self.pinned_stable_tab_ids.iter())
+            .chain(self.stable_tab_id_to_cycle.keys())
+            .chain(self.deferred_until_active.keys())
+            .copied()
+            .filter(|stable_tab_id| !live_ids.contains(stable_tab_id))
+            .collect();
+
+        self.stable_tab_id_to_missing_rebuilds
+            .retain(|stable_tab_id, _| stored_ids.contains(stable_tab_id));
+        for stable_tab_id in stored_ids {
+            let missing_rebuilds = self
+                .stable_tab_id_to_missing_rebuilds
+                .entry(stable_tab_id)
+                .or_insert(0);
+            *missing_rebuilds += 1;
+            if *missing_rebuilds > threshold {
+                self.log(LogLevel::Info, || {
+                    format!(
+                        "GC: Dropping state of stable_tab_id {}, not seen for {} rebuilds",
+                        stable_tab_id, threshold
+                    )
+                });
+                self.forget_stable_tab_id(stable_tab_id);
+            }
+        }
+    }
+
+    /// Highest stable tab ID currently assigned to any tab
+    fn max_stable_tab_id(&self) -> u32 {
+        self.pane_to_stable_tab_id
+            .values()
+            .chain(self.paneless_tab_to_stable_id.values())
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The ID for a new tab. Like Zellij's own tab IDs, it is `max + 1` over the
+    /// live IDs (and `floor`) while any tab is tracked. Once none is, numbering
+    /// continues above stable_id_high_water, or starts from 1 again with
+    /// reset_ids_when_empty.
+    fn next_stable_tab_id(&self, floor: u32) -> u32 {
+        let max_stable_id = self.max_stable_tab_id().max(floor);
+        if max_stable_id == 0 && !self.config.reset_ids_when_empty {
+            self.stable_id_high_water + 1
+        } else {
+            max_stable_id + 1
+        }
+    }
+
+    /// Handle the home-tab pipe: where the tab marked with set_as_home is now.
+    /// All fields are null when no tab is marked.
+    fn home_tab(&self) -> serde_json::Value {
+        let tab_position = self
+            .home_stable_tab_id
+            .and_then(|stable_tab_id| self.stable_tab_id_position(stable_tab_id));
+        let name = tab_position
+            .and_then(|tab_position| self.tabs.get(tab_position))
+            .map(|tab| tab.name.clone());
+
+        json!({
+            "ok": true,
+            "stable_id": self.home_stable_tab_id,
+            "tab_position": tab_position,
+            "name": name,
+        })
+    }
+
+    /// Handle the resync-ids pipe: renumber stable IDs 1..n from left to right,
+    /// the IDs Zellij's auto-increment gives tabs that were never closed or moved.
+    /// Formats and tracking state follow their tab to its new ID.
+    fn resync_ids(&mut self) -> serde_json::Value {
+        let mut mapping: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut changed = Vec::new();
+        for tab_position in 0..self.tabs.len() {
+            let old_id = self.stable_tab_id_at(tab_position);
+            let new_id = tab_position as u32 + 1;
+            if let Some(old_id) = old_id {
+                mapping.insert(old_id, new_id);
+            }
+            if old_id != Some(new_id) {
+                changed.push(json!({
+                    "tab_position": tab_position,
+                    "old_id": old_id,
+                    "new_id": new_id,
+                }));
+            }
+        }
+
+        self.pane_to_stable_tab_id = self
+            .pane_to_tab
+            .iter()
+            .map(|(&pane_id, &tab_position)| (pane_id, tab_position as u32 + 1))
+            .collect();
+        for (&tab_position, stable_id) in self.paneless_tab_to_stable_id.iter_mut() {
+            *stable_id = tab_position as u32 + 1;
+        }
+        self.refresh_position_to_stable_tab_id();
+        self.rekey_stable_tab_ids(&mapping);
+
+        self.log(LogLevel::Info, || {
+            format!("PIPE: Resynced stable IDs, {} changed", changed.len())
+        });
+
+        json!({ "ok": true, "changed": changed })
+    }
+
+    /// Move all state stored per stable tab ID to the new IDs in the mapping,
+    /// dropping state of IDs that aren't in it
+    fn rekey_stable_tab_ids(&mut self, mapping: &BTreeMap<u32, u32>) {
+        fn rekey<V>(map: &mut BTreeMap<u32, V>, mapping: &BTreeMap<u32, u32>) {
+            *map = std::mem::take(map)
+                .into_iter()
+                .filter_map(|(id, value)| Some((*mapping.get(&id)?, value)))
+                .collect();
+        }
+
+        // Deferred renames also carry the tab ID to pass to rename_tab
+        self.pending_renames = std::mem::take(&mut self.pending_renames)
+            .into_iter()
+            .filter_map(|(id, (tab_id, name, source))| {
+                let new_id = *mapping.get(&id)?;
+                let tab_id = if tab_id == id { new_id } else { tab_id };
+                Some((new_id, (tab_id, name, source)))
+            })
+            .collect();
+        self.disabled_autoupdate = self
+            .disabled_autoupdate
+            .iter()
+            .filter_map(|id| mapping.get(id).copied())
+            .collect();
+        self.pinned_stable_tab_ids = self
+            .pinned_stable_tab_ids
+            .iter()
+            .filter_map(|id| mapping.get(id).copied())
+            .collect();
+        self.home_stable_tab_id = self
+            .home_stable_tab_id
+            .and_then(|id| mapping.get(&id).copied());
+
+        rekey(&mut self.stable_tab_id_to_format, mapping);
+        rekey(&mut self.stable_tab_id_to_last_position, mapping);
+        rekey(&mut self.stable_tab_id_to_last_active, mapping);
+        rekey(&mut self.stable_tab_id_to_last_sync, mapping);
+        rekey(&mut self.stable_tab_id_to_last_pane_count, mapping);
+        rekey(&mut self.stable_tab_id_to_last_exit_code, mapping);
+        rekey(&mut self.stable_tab_id_to_last_layout, mapping);
+        rekey(&mut self.stable_tab_id_to_last_active_pane_title, mapping);
+        rekey(&mut self.stable_tab_id_to_last_tab_count, mapping);
+        rekey(&mut self.stable_tab_id_to_last_project_dir, mapping);
+        rekey(&mut self.stable_tab_id_to_last_date, mapping);
+        rekey(&mut self.stable_tab_id_to_last_session_short, mapping);
+        rekey(&mut self.stable_tab_id_to_cycle, mapping);
+        rekey(&mut self.deferred_until_active, mapping);
+        rekey(&mut self.stable_tab_id_to_history, mapping);
+        rekey(&mut self.stable_tab_id_to_last_rename_at, mapping);
+        rekey(&mut self.stable_tab_id_to_created_at, mapping);
+        rekey(&mut self.stable_tab_id_to_neighbor_hash, mapping);
+        rekey(&mut self.stable_tab_id_to_neighbor_cascade, mapping);
+        rekey(&mut self.stable_tab_id_to_applied_name, mapping);
+        rekey(&mut self.stable_tab_id_to_previous_name, mapping);
+        rekey(&mut self.stable_tab_id_to_rename_count, mapping);
+        rekey(&mut self.stable_tab_id_to_missing_rebuilds, mapping);
+    }
+
+    /// Drop the stored format and tracking state of a tab that no longer exists.
+    /// State added per stable tab ID must also be moved in rekey_stable_tab_ids.
+    fn forget_stable_tab_id(&mut self, stable_tab_id: u32) {
+        self.stable_tab_id_to_format.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_position.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_active.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_sync.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_pane_count.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_exit_code.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_layout.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_active_pane_title
+            .remove(&stable_tab_id);
+        self.stable_tab_id_to_last_tab_count.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_project_dir
+            .remove(&stable_tab_id);
+        self.stable_tab_id_to_last_date.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_session_short
+            .remove(&stable_tab_id);
+        self.stable_tab_id_to_cycle.remove(&stable_tab_id);
+        self.deferred_until_active.remove(&stable_tab_id);
+        self.stable_tab_id_to_history.remove(&stable_tab_id);
+        self.stable_tab_id_to_last_rename_at.remove(&stable_tab_id);
+        self.pending_renames.remove(&stable_tab_id);
+        self.stable_tab_id_to_created_at.remove(&stable_tab_id);
+        self.stable_tab_id_to_neighbor_hash.remove(&stable_tab_id);
+        self.stable_tab_id_to_neighbor_cascade
+            .remove(&stable_tab_id);
+        self.stable_tab_id_to_applied_name.remove(&stable_tab_id);
+        self.stable_tab_id_to_previous_name.remove(&stable_tab_id);
+        self.stable_tab_id_to_rename_count.remove(&stable_tab_id);
+        self.disabled_autoupdate.remove(&stable_tab_id);
+        self.pinned_stable_tab_ids.remove(&stable_tab_id);
+        if self.home_stable_tab_id == Some(stable_tab_id) {
+            self.home_stable_tab_id = None;
+        }
+        self.stable_tab_id_to_missing_rebuilds
+            .remove(&stable_tab_id);
+    }
+
+    /// Auto-update tab names when positions change
+    /// For tabs with stored format strings, check if their position changed
+    /// and re-evaluate the format string with the new position
+    fn auto_update_tab_names(&mut self) {
+        if self.auto_update_paused {
+            self.log(LogLevel::Debug, || {
+                "AUTO-UPDATE: Paused, skipping".to_string()
+            });
+            return;
+        }
+
+        // Tracking is left untouched, so changes are picked up once enough tabs are back
+        if self.tabs.len() < self.config.min_tabs_for_autoupdate {
+            self.log(LogLevel::Debug, || {
+                format!(
+                    "AUTO-UPDATE: Only {} tabs open (min_tabs_for_autoupdate={}), skipping",
+                    self.tabs.len(),
+                    self.config.min_tabs_for_autoupdate
+                )
+            });
+            return;
+        }
+
+        // Re-evaluate and rename tabs that moved
+        let mut renames_issued = 0;
+        for AutoUpdateCandidate {
+            stable_tab_id,
+            tab_position: new_position,
+            tab_format,
+            neighbor_only,
+        } in self.auto_update_candidates()
+        {
+            if renames_issued >= MAX_AUTO_RENAMES_PER_UPDATE {
+                self.log(LogLevel::Warn, || {
+                    format!(
+                        "auto-update issued {} renames in one pass, deferring the rest",
+                        renames_issued
+                    )
+                });
+                break;
+            }
+
+            if let Ok(new_name) = self.auto_update_name(&tab_format, new_position) {
+                let unchanged = self
+                    .tabs
+                    .get(new_position)
+                    .is_some_and(|tab| tab.name == new_name);
+
+                // A neighbor-triggered render that settles on the current name ends
+                // the cascade; otherwise count it towards MAX_NEIGHBOR_CASCADE
+                if !neighbor_only || unchanged {
+                    self.stable_tab_id_to_neighbor_cascade
+                        .remove(&stable_tab_id);
+                } else {
+                    *self
+                        .stable_tab_id_to_neighbor_cascade
+                        .entry(stable_tab_id)
+                        .or_insert(0) += 1;
+                }
+
+                // Only call rename_tab when the name differs from both the one shown
+                // and the last one sent (maybe not yet shown). Anything else would
+                // only trigger another TabUpdate and can make the tab bar flicker.
+                // Until Zellij's pane and tab updates agree, the name shown at a
+                // moved tab's position may be another tab's, so once the plugin has
+                // sent this tab a name, only that one counts.
+                let last_emitted = self.last_emitted_name(stable_tab_id);
+                let already_emitted = last_emitted == Some(&new_name);
+
+                let skip = already_emitted || (unchanged && last_emitted.is_none());
+                if !skip {
+                    renames_issued += 1;
+                    self.metrics.auto_updates_triggered += 1;
+                    self.log(LogLevel::Info, || {
+                        format!(
+                            "AUTO-UPDATE: Renaming stable_tab_id {} to {:?} (position {})",
+                            stable_tab_id, new_name, new_position
+                        )
+                    });
+
+                    self.apply_rename(stable_tab_id, Some(stable_tab_id), new_name, "auto-update");
+                }
+                self.record_rendered_state(stable_tab_id, new_position);
+            }
+        }
+    }
+
+    /// The tabs auto-update would re-render: every tab with a stored format (and
+    /// auto-update not disabled or pinned) whose position or any rendered state
+    /// changed since it was last rendered. Nothing is modified.
+    fn auto_update_candidates(&self) -> Vec<AutoUpdateCandidate> {
+        // Build stable_tab_id -> current_display_index mapping
+        let mut stable_tab_id_to_current_position: BTreeMap<u32, usize> = BTreeMap::new();
+        for (&pane_id, &stable_tab_id) in &self.pane_to_stable_tab_id {
+            if let Some(&current_position) = self.pane_to_tab.get(&pane_id) {
+                stable_tab_id_to_current_position.insert(stable_tab_id, current_position);
+            }
+        }
+        for (&position, &stable_tab_id) in &self.paneless_tab_to_stable_id {
+            stable_tab_id_to_current_position.insert(stable_tab_id, position);
+        }
+
+        // Check each tab with a stored format for position, active state or
+        // neighbor name changes
+        self
+            .stable_tab_id_to_format
+            .iter()
+            .filter(|(stable_tab_id, _)| {
+                !self.disabled_autoupdate.contains(stable_tab_id)
+                    && !self.pinned_stable_tab_ids.contains(stable_tab_id)
+            })
+            .filter_map(|(&stable_tab_id, tab_format)| {
+                let current_position = stable_tab_id_to_current_position.get(&stable_tab_id)?;
+                let last_position = self.stable_tab_id_to_last_position.get(&stable_tab_id)?;
+
+                let position_changed = current_position != last_position;
+                let active_changed = tab_format.uses_placeholder("active_marker")
+                    && self.stable_tab_id_to_last_active.get(&stable_tab_id)
+                        != Some(&self.is_tab_active(*current_position));
+                let sync_changed = tab_format.uses_placeholder("sync_marker")
+                    && self.stable_tab_id_to_last_sync.get(&stable_tab_id)
+                        != Some(&self.is_tab_syncing(*current_position));
+
+                let pane_count_changed = (!tab_format.templates.is_empty()
+                    || tab_format.uses_placeholder("pane_count"))
+                    && self.stable_tab_id_to_last_pane_count.get(&stable_tab_id)
+                        != Some(&self.tab_pane_count(*current_position));
+
+                let exit_code_changed = tab_format.uses_placeholder("pane_exit_code")
+                    && self.stable_tab_id_to_last_exit_code.get(&stable_tab_id)
+                        != Some(&self.tab_exit_code(*current_position));
+
+                let layout_changed = tab_format.uses_placeholder("layout_marker")
+                    && self.stable_tab_id_to_last_layout.get(&stable_tab_id)
+                        != Some(&self.tab_layout(*current_position));
+
+                let active_pane_title_changed = tab_format.uses_placeholder("active_pane_title")
+                    && self
+                        .stable_tab_id_to_last_active_pane_title
+                        .get(&stable_tab_id)
+                        != Some(&self.active_pane_title(*current_position));
+
+                let tab_count_changed = tab_format.uses_placeholder("tab_position_from_end")
+                    && self.stable_tab_id_to_last_tab_count.get(&stable_tab_id)
+                        != Some(&self.tabs.len());
+
+                let project_dir_changed = tab_format.uses_placeholder("project_dir")
+                    && self.stable_tab_id_to_last_project_dir.get(&stable_tab_id)
+                        != Some(&self.project_dir(*current_position));
+
+                let date_changed = tab_format.follows_date()
+                    && self.stable_tab_id_to_last_date.get(&stable_tab_id)
+                        != Some(&current_date());
+
+                let session_short_changed = tab_format.uses_placeholder("session_short")
+                    && self
+                        .stable_tab_id_to_last_session_short
+                        .get(&stable_tab_id)
+                        != Some(&self.session_short());
+
+                let neighbor_changed = (tab_format.uses_placeholder("prev_tab_name")
+                    || tab_format.uses_placeholder("next_tab_name"))
+                    && self.stable_tab_id_to_neighbor_hash.get(&stable_tab_id)
+                        != Some(&self.neighbor_names_hash(*current_position))
+                    && self
+                        .stable_tab_id_to_neighbor_cascade
+                        .get(&stable_tab_id)
+                        .is_none_or(|&depth| depth < MAX_NEIGHBOR_CASCADE);
+
+                if position_changed
+                    || active_changed
+                    || sync_changed
+                    || pane_count_changed
+                    || exit_code_changed
+                    || layout_changed
+                    || active_pane_title_changed
+                    || tab_count_changed
+                    || project_dir_changed
+                    || date_changed
+                    || session_short_changed
+                    || neighbor_changed
+                {
+                    self.log(LogLevel::Debug, || format!(
+                        "AUTO-UPDATE: stable_tab_id {} moved from position {} to {} (active_changed={}, neighbor_changed={})",
+                        stable_tab_id, last_position, current_position, active_changed, neighbor_changed
+                    ));
+
+                    let neighbor_only = !position_changed
+                        && !active_changed
+                        && !sync_changed
+                        && !pane_count_changed
+                        && !exit_code_changed
+                        && !layout_changed
+                        && !active_pane_title_changed
+                        && !tab_count_changed
+                        && !project_dir_changed
+                        && !date_changed
+                        && !session_short_changed;
+                    Some(AutoUpdateCandidate {
+                        stable_tab_id,
+                        tab_position: *current_position,
+                        tab_format: tab_format.clone(),
+                        neighbor_only,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The name auto-update sends for a tab rendered at tab_position
+    fn auto_update_name(
+        &self,
+        tab_format: &TabFormat,
+        tab_position: usize,
+    ) -> Result<String, String> {
+        self.format_tab_name(tab_format, tab_position)
+            .map(|name| self.emitted_name(tab_format, &name))
+    }
+
+    /// Handle the preview-autoupdate pipe: list the renames the next auto-update
+    /// would issue, without renaming anything or updating the tracked state
+    fn preview_auto_update(&self) -> serde_json::Value {
+        let changes: Vec<serde_json::Value> = self
+            .auto_update_candidates()
+            .into_iter()
+            .filter_map(|candidate| {
+                let proposed_name = self
+                    .auto_update_name(&candidate.tab_format, candidate.tab_position)
+                    .ok()?;
+                let old_name = self.tabs.get(candidate.tab_position).map(|tab| &tab.name);
+                if old_name == Some(&proposed_name)
+                    || self.last_emitted_name(candidate.stable_tab_id) == Some(&proposed_name)
+                {
+                    return None;
+                }
+                Some(json!({
+                    "stable_id": candidate.stable_tab_id,
+                    "tab_position": candidate.tab_position,
+                    "old_name": old_name,
+                    "proposed_name": proposed_name,
+                }))
+            })
+            .take(MAX_AUTO_RENAMES_PER_UPDATE)
+            .collect();
+
+        json!({
+            "ok": true,
+            "paused": self.auto_update_paused,
+            "min_tabs_met": self.tabs.len() >= self.config.min_tabs_for_autoupdate,
+            "changes": changes,
+        })
+    }
+
+    /// Resolve the tab targeted by a rename request to its display index and stable tab ID.
+    /// The tab is addressed by pane_id, by tab_position, or by tab_offset relative to
+    /// the focused tab.
+    fn resolve_rename_target(
+        &self,
+        rename_payload: &RenamePayload,
+    ) -> Result<(usize, Option<u32>), PipeError> {
+        if let Some(pane_id) = &rename_payload.pane_id {
+            let pane_id = parse_pane_id("change-tab-name", pane_id)?;
+
+            // Plugin pane ids live in their own namespace and never get stable IDs
+            if rename_payload.allow_plugin_pane {
+                let Some(tab_position) = self.resolve_plugin_pane_tab_position(pane_id) else {
+                    return Err(PipeError::new(
+                        "PANE_NOT_FOUND",
+                        format!("change-tab-name: plugin pane {} not found", pane_id),
+                    ));
+                };
+                check_expected_position(rename_payload, pane_id, tab_position)?;
+                return Ok((tab_position, self.stable_tab_id_at(tab_position)));
+            }
+
+            let Some(tab_position) =
+                self.resolve_tab_position(pane_id, rename_payload.include_suppressed)
+            else {
+                // Right after load, pipes can arrive before Zellij sent the first PaneUpdate
+                if self.pane_to_tab.is_empty() && self.panes.panes.is_empty() {
+                    return Err(PipeError::new(
+                        "NOT_READY",
+                        format!(
+                            "change-tab-name: the plugin has not received the pane list from Zellij yet, so pane {} can't be resolved. Retry in a moment",
+                            pane_id
+                        ),
+                    ));
+                }
+                return Err(PipeError::new("PANE_NOT_FOUND", format!("change-tab-name: pane {} not found in mapping (pane_to_tab has {} entries, tabs has {} entries)",
+                    pane_id, self.pane_to_tab.len(), self.tabs.len())));
+            };
+            check_expected_position(rename_payload, pane_id, tab_position)?;
+
+            // Suppressed panes have no stable ID of their own, so fall back to the tab's
+            let stable_tab_id = self
+                .pane_to_stable_tab_id
+                .get(&pane_id)
+                .copied()
+                .or_else(|| self.stable_tab_id_at(tab_position));
+
+            return Ok((tab_position, stable_tab_id));
+        }
+
+        if let Some(tab_position) = rename_payload.tab_position {
+            if tab_position >= self.tabs.len() {
+                return Err(PipeError::new(
+                    "TAB_NOT_FOUND",
+                    format!(
+                        "change-tab-name: tab at display index {} not found (tabs has {} entries)",
+                        tab_position,
+                        self.tabs.len()
+                    ),
+                ));
+            }
+
+            return Ok((tab_position, self.stable_tab_id_at(tab_position)));
+        }
+
+        if let Some(tab_number) = rename_payload.tab_number {
+            if tab_number == 0 {
+                return Err(PipeError::new(
+                    "INVALID_TAB_NUMBER",
+                    "change-tab-name: tab_number is 1-indexed, use tab_position for 0-indexed positions",
+                ));
+            }
+            if tab_number > self.tabs.len() {
+                return Err(PipeError::new(
+                    "TAB_NOT_FOUND",
+                    format!(
+                        "change-tab-name: tab number {} not found (tabs has {} entries)",
+                        tab_number,
+                        self.tabs.len()
+                    ),
+                ));
+            }
+
+            let tab_position = tab_number - 1;
+            return Ok((tab_position, self.stable_tab_id_at(tab_position)));
+        }
+
+        if let Some(offset) = rename_payload.tab_offset {
+            let Some(focused_position) = self.tabs.iter().position(|t| t.active) else {
+                return Err(PipeError::new(
+                    "NO_ACTIVE_TAB",
+                    "change-tab-name: no active tab to apply tab_offset to",
+                ));
+            };
+
+            let target = focused_position as i64 + offset as i64;
+            let last = self.tabs.len() as i64 - 1;
+            let tab_position = if (0..=last).contains(&target) {
+                target as usize
+            } else if rename_payload.clamp {
+                target.clamp(0, last) as usize
+            } else {
+                return Err(PipeError::new(
+                    "OFFSET_OUT_OF_RANGE",
+                    format!(
+                        "change-tab-name: tab_offset {} from focused tab {} is out of range (tabs has {} entries)",
+                        offset, focused_position, self.tabs.len()
+                    ),
+                ));
+            };
+
+            return Ok((tab_position, self.stable_tab_id_at(tab_position)));
+        }
+
+        Err(PipeError::new(
+            "MISSING_TARGET",
+            "change-tab-name: one of pane_id, tab_position, tab_number or tab_offset is required",
+        ))
+    }
+
+    /// Resolve a pane to the display index of its tab.
+    /// With include_suppressed, panes excluded from pane_to_tab because they are
+    /// suppressed (e.g. swapped out of the current layout) are also looked up.
+    fn resolve_tab_position(&self, pane_id: u32, include_suppressed: bool) -> Option<usize> {
+        if let Some(&tab_position) = self.pane_to_tab.get(&pane_id) {
+            return Some(tab_position);
+        }
+        if !include_suppressed {
+            return None;
+        }
+
+        self.tabs.iter().position(|tab| {
+            self.panes
+                .panes
+                .get(&tab.position)
+                .is_some_and(|pane_list| {
+                    pane_list
+                        .iter()
+                        .any(|p| p.id == pane_id && is_tracked_pane(p, true))
+                })
+        })
+    }
+
+    /// Resolve a plugin pane to the display index of its tab
+    fn resolve_plugin_pane_tab_position(&self, plugin_pane_id: u32) -> Option<usize> {
+        self.tabs.iter().position(|tab| {
+            self.panes
+                .panes
+                .get(&tab.position)
+                .is_some_and(|pane_list| {
+                    pane_list
+                        .iter()
+                        .any(|p| p.is_plugin && p.id == plugin_pane_id)
+                })
+        })
+    }
+
+    /// Current display index of the tab with the given stable tab ID
+    fn stable_tab_id_position(&self, stable_tab_id: u32) -> Option<usize> {
+        self.pane_to_stable_tab_id
+            .iter()
+            .find(|(_, &id)| id == stable_tab_id)
+            .and_then(|(pane_id, _)| self.pane_to_tab.get(pane_id).copied())
+            .or_else(|| {
+                self.paneless_tab_to_stable_id
+                    .iter()
+                    .find(|(_, &id)| id == stable_tab_id)
+                    .map(|(&position, _)| position)
+            })
+    }
+
+    /// Store a branch reported by the git_branch worker and re-render the tabs
+    /// whose format uses {git_branch} for that working directory
+    fn update_git_branch(&mut self, result: GitBranchResult) {
+        let GitBranchResult { cwd, branch } = result;
+        if self.cwd_to_git_branch.get(&cwd) == Some(&branch) {
+            return;
+        }
+        self.cwd_to_git_branch.insert(cwd.clone(), branch);
+
+        // The branch is still cached, so resuming auto-update picks it up
+        if self.auto_update_paused {
+            self.log(LogLevel::Debug, || {
+                "GIT-BRANCH: Auto-update paused, skipping".to_string()
+            });
+            return;
+        }
+
+        // Same opt-outs as auto-update
+        let tabs_to_update: Vec<(u32, TabFormat)> = self
+            .stable_tab_id_to_format
+            .iter()
+            .filter(|(stable_tab_id, tab_format)| {
+                !self.disabled_autoupdate.contains(stable_tab_id)
+                    && !self.pinned_stable_tab_ids.contains(stable_tab_id)
+                    && tab_format.cwd.as_deref() == Some(cwd.as_str())
+                    && tab_format.uses_placeholder("git_branch")
+            })
+            .map(|(&stable_tab_id, tab_format)| (stable_tab_id, tab_format.clone()))
+            .collect();
+
+        for (stable_tab_id, tab_format) in tabs_to_update {
+            let Some(tab_position) = self.stable_tab_id_position(stable_tab_id) else {
+                continue;
+            };
+            if let Ok(new_name) = self.auto_update_name(&tab_format, tab_position) {
+                if self.tabs.get(tab_position).map(|t| &t.name) != Some(&new_name) {
+                    self.apply_rename(stable_tab_id, Some(stable_tab_id), new_name, "git-branch");
+                }
+                self.record_rendered_state(stable_tab_id, tab_position);
+            }
+        }
+    }
+
+    /// The stable tab ID of the tab at the given display index
+    fn stable_tab_id_at(&self, tab_position: usize) -> Option<u32> {
+        self.position_to_stable_tab_id
+            .get(tab_position)
+            .copied()
+            .flatten()
+    }
+
+    /// Recompute position_to_stable_tab_id from pane_to_tab, pane_to_stable_tab_id
+    /// and paneless_tab_to_stable_id. Must run after any of them changes.
+    fn refresh_position_to_stable_tab_id(&mut self) {
+        let mut position_to_stable_tab_id = vec![None; self.tabs.len()];
+        let assigned = self
+            .pane_to_tab
+            .iter()
+            .map(|(pane_id, &position)| {
+                (position, self.pane_to_stable_tab_id.get(pane_id).copied())
+            })
+            .chain(
+                self.paneless_tab_to_stable_id
+                    .iter()
+                    .map(|(&position, &stable_id)| (position, Some(stable_id))),
+            );
+        // The mapping can briefly hold panes of tabs past the end of self.tabs
+        for (position, stable_id) in assigned {
+            if position >= position_to_stable_tab_id.len() {
+                position_to_stable_tab_id.resize(position + 1, None);
+            }
+            // All panes of a tab share its ID, so the first one is as good as any
+            position_to_stable_tab_id[position] = position_to_stable_tab_id[position].or(stable_id);
+        }
+        self.position_to_stable_tab_id = position_to_stable_tab_id;
+    }
+
+    /// Rename a tab via rename_tab(tab_id), rate limited per stable tab ID.
+    ///
+    /// If the tab was renamed less than rename_interval ago, the rename is
+    /// deferred and coalesced with any later request for the same tab; a
+    /// timer flushes it once the interval has passed. Renames of other tabs
+    /// are not delayed. The source (e.g. "pipe" or "auto-update") is written to log_file.
+    fn apply_rename(
+        &mut self,
+        tab_id: u32,
+        stable_tab_id: Option<u32>,
+        name: String,
+        source: &str,
+    ) {
+        let Some(stable_tab_id) = stable_tab_id else {
+            self.audit_rename(None, None, &name, source);
+            rename_tab(tab_id, &name);
+            self.metrics.renames_applied += 1;
+            return;
+        };
+
+        if !self
+            .stable_tab_id_to_previous_name
+            .contains_key(&stable_tab_id)
+        {
+            if let Some(tab) = self
+                .stable_tab_id_position(stable_tab_id)
+                .and_then(|tab_position| self.tabs.get(tab_position))
+            {
+                self.stable_tab_id_to_previous_name
+                    .insert(stable_tab_id, tab.name.clone());
+            }
+        }
+
+        if self.is_name_being_edited(stable_tab_id) {
+            self.log(LogLevel::Info, || {
+                format!(
+                    "EDITING: Deferring rename of stable_tab_id {} to {:?} until its name is no longer edited",
+                    stable_tab_id, name
+                )
+            });
+            self.pending_renames
+                .insert(stable_tab_id, (tab_id, name, source.to_string()));
+            return;
+        }
+
+        let interval = self.config.rename_interval;
+        if let Some(last_rename_at) = self.stable_tab_id_to_last_rename_at.get(&stable_tab_id) {
+            let elapsed = last_rename_at.elapsed();
+            if elapsed < interval {
+                self.log(LogLevel::Info, || {
+                    format!(
+                        "RATE-LIMIT: Deferring rename of stable_tab_id {} to {:?}",
+                        stable_tab_id, name
+                    )
+                });
+
+                if self
+                    .pending_renames
+                    .insert(stable_tab_id, (tab_id, name, source.to_string()))
+                    .is_none()
+                {
+                    set_timeout((interval - elapsed).as_secs_f64());
+                }
+                return;
+            }
+        }
+
+        self.pending_renames.remove(&stable_tab_id);
+        rename_tab(tab_id, &name);
+        self.record_rename(stable_tab_id, &name, source);
+    }
+
+    /// Whether the user is currently typing a new name for this tab in RenameTab
+    /// mode. Zellij only renames the active tab in that mode.
+    fn is_name_being_edited(&self, stable_tab_id: u32) -> bool {
+        self.editing_active_tab_name
+            && self
+                .stable_tab_id_position(stable_tab_id)
+                .is_some_and(|tab_position| self.is_tab_active(tab_position))
+    }
+
+    /// Bookkeeping after rename_tab was called for a stable tab ID
+    fn record_rename(&mut self, stable_tab_id: u32, name: &str, source: &str) {
+        // Zellij hasn't confirmed the rename yet, so the tab still shows the old name
+        let old_name = self
+            .stable_tab_id_position(stable_tab_id)
+            .and_then(|tab_position| self.tabs.get(tab_position))
+            .map(|tab| tab.name.clone());
+        self.audit_rename(Some(stable_tab_id), old_name.as_deref(), name, source);
+        self.metrics.renames_applied += 1;
+
+        self.record_applied_name(stable_tab_id, name);
+        self.record_history(stable_tab_id, name, source);
+        self.stable_tab_id_to_last_rename_at
+            .insert(stable_tab_id, Instant::now());
+        *self
+            .stable_tab_id_to_rename_count
+            .entry(stable_tab_id)
+            .or_default() += 1;
+    }
+
+    /// Apply the rename deferred by defer_until_active for the focused tab, if any
+    fn apply_deferred_until_active(&mut self) {
+        let Some(tab_position) = self.tabs.iter().position(|tab| tab.active) else {
+            return;
+        };
+        let Some(stable_tab_id) = self.stable_tab_id_at(tab_position) else {
+            return;
+        };
+        let Some(rename_payload) = self.deferred_until_active.remove(&stable_tab_id) else {
+            return;
+        };
+
+        self.log(LogLevel::Info, || {
+            format!(
+                "PIPE: stable_tab_id {} is focused, applying its deferred rename",
+                stable_tab_id
+            )
+        });
+        if let Err(error) = self.rename_target(rename_payload, (tab_position, Some(stable_tab_id)))
+        {
+            self.show_error(&error.message);
+            *self.metrics.errors_by_code.entry(error.code).or_default() += 1;
+        }
+    }
+
+    /// Apply deferred renames whose rate limit interval has passed
+    fn flush_pending_renames(&mut self) {
+        let interval = self.config.rename_interval;
+        let mut next_timeout: Option<Duration> = None;
+
+        for (stable_tab_id, (tab_id, name, source)) in std::mem::take(&mut self.pending_renames) {
+            let elapsed = self
+                .stable_tab_id_to_last_rename_at
+                .get(&stable_tab_id)
+                .map_or(interval, Instant::elapsed);
+
+            if self.is_name_being_edited(stable_tab_id) {
+                // Flushed by the TabUpdate that follows the end of the edit
+                self.pending_renames
+                    .insert(stable_tab_id, (tab_id, name, source));
+            } else if elapsed >= interval {
+                self.log(LogLevel::Info, || {
+                    format!(
+                        "RATE-LIMIT: Flushing rename of stable_tab_id {} to {:?}",
+                        stable_tab_id, name
+                    )
+                });
+
+                rename_tab(tab_id, &name);
+                self.record_rename(stable_tab_id, &name, &source);
+            } else {
+                let remaining = interval - elapsed;
+                next_timeout = Some(next_timeout.map_or(remaining, |t| t.min(remaining)));
+                self.pending_renames
+                    .insert(stable_tab_id, (tab_id, name, source));
+            }
+        }
+
+        if let Some(timeout) = next_timeout {
+            set_timeout(timeout.as_secs_f64());
+        }
+    }
+
+    /// The latest name sent (or queued) for a tab by the plugin
+    fn last_emitted_name(&self, stable_tab_id: u32) -> Option<&String> {
+        self.pending_renames
+            .get(&stable_tab_id)
+            .map(|(_, name, _)| name)
+            .or_else(|| {
+                self.stable_tab_id_to_applied_name
+                    .get(&stable_tab_id)
+                    .map(|(name, _)| name)
+            })
+    }
+
+    /// Remember a name sent to rename_tab. Until a TabUpdate shows it, the tab's
+    /// current name and the names sent before it are still expected.
+    fn record_applied_name(&mut self, stable_tab_id: u32, name: &str) {
+        let mut in_flight = match self.stable_tab_id_to_applied_name.remove(&stable_tab_id) {
+            Some((applied, mut in_flight)) => {
+                in_flight.push(applied);
+                in_flight
+            }
+            None => Vec::new(),
+        };
+        if let Some(tab) = self
+            .stable_tab_id_position(stable_tab_id)
+            .and_then(|tab_position| self.tabs.get(tab_position))
+        {
+            in_flight.push(tab.name.clone());
+        }
+
+        self.stable_tab_id_to_applied_name
+            .insert(stable_tab_id, (name.to_string(), in_flight));
+    }
+
+    /// Stop managing tabs whose name was changed by someone other than the plugin,
+    /// so auto-update doesn't overwrite manual renames.
+    ///
+    /// Tabs that moved since they were last rendered are skipped: Zellij reports
+    /// panes and tabs in separate events, so until both arrive the name at a
+    /// moved tab's new position may still be another tab's.
+    fn yield_externally_renamed_tabs(&mut self) {
+        let current_names: Vec<(u32, String)> = self
+            .stable_tab_id_to_applied_name
+            .keys()
+            .filter(|stable_tab_id| !self.pending_renames.contains_key(stable_tab_id))
+            .filter_map(|&stable_tab_id| {
+                let tab_position = self.stable_tab_id_position(stable_tab_id)?;
+                if self.stable_tab_id_to_last_position.get(&stable_tab_id) != Some(&tab_position) {
+                    return None;
+                }
+                Some((stable_tab_id, self.tabs.get(tab_position)?.name.clone()))
+            })
+            .collect();
+
+        let mut renamed_externally = Vec::new();
+        for (stable_tab_id, current_name) in current_names {
+            let Some((applied, in_flight)) =
+                self.stable_tab_id_to_applied_name.get_mut(&stable_tab_id)
+            else {
+                continue;
+            };
+            if current_name == *applied {
+                in_flight.clear();
+            } else if !in_flight.contains(&current_name) {
+                renamed_externally.push(stable_tab_id);
+            }
+        }
+
+        for stable_tab_id in renamed_externally {
+            self.log(LogLevel::Info, || {
+                format!(
+                    "AUTO-UPDATE: stable_tab_id {} was renamed externally, dropping its format",
+                    stable_tab_id
+                )
+            });
+
+            self.stable_tab_id_to_format.remove(&stable_tab_id);
+            self.stable_tab_id_to_applied_name.remove(&stable_tab_id);
+        }
+    }
+
+    /// Append an applied rename to the configured log_file as a JSON line. Failing to
+    /// write only logs an error, since the rename itself already happened.
+    fn audit_rename(
+        &self,
+        stable_tab_id: Option<u32>,
+        old_name: Option<&str>,
+        new_name: &str,
+        source: &str,
+    ) {
+        use std::io::Write;
+
+        let Some(path) = &self.config.log_file else {
+            return;
+        };
+        let line = json!({
+            "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "stable_id": stable_tab_id,
+            "old_name": old_name,
+            "new_name": new_name,
+            "source": source,
+        });
+
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(error) = written {
+            self.show_error(&format!("log_file: can't write to {}: {}", path, error));
+        }
+    }
+
+    /// Append an applied name and its source to a tab's rename history, dropping the
+    /// oldest entry when full
+    fn record_history(&mut self, stable_tab_id: u32, name: &str, source: &str) {
+        let history = self
+            .stable_tab_id_to_history
+            .entry(stable_tab_id)
+            .or_default();
+        if history.len() >= MAX_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back((name.to_string(), source.to_string()));
+    }
+
+    /// Whether the tab at the given display index is the active tab
+    fn is_tab_active(&self, tab_position: usize) -> bool {
+        self.tabs.get(tab_position).is_some_and(|t| t.active)
+    }
+
+    /// Number of tracked panes in the tab at the given display index
+    fn tab_pane_count(&self, tab_position: usize) -> usize {
+        self.pane_to_tab
+            .values()
+            .filter(|&&position| position == tab_position)
+            .count()
+    }
+
+    /// Exit code of the commands that finished in the tab's terminal panes: the
+    /// first non-zero one if any failed, else 0. None if no command has exited.
+    fn tab_exit_code(&self, tab_position: usize) -> Option<i32> {
+        let exit_codes: Vec<i32> = self
+            .tabs
+            .get(tab_position)
+            .and_then(|tab| self.panes.panes.get(&tab.position))
+            .into_iter()
+            .flatten()
+            .filter(|pane_info| !pane_info.is_plugin)
+            .filter_map(|pane_info| pane_info.exit_status)
+            .collect();
+
+        exit_codes
+            .iter()
+            .copied()
+            .find(|&code| code != 0)
+            .or_else(|| exit_codes.first().copied())
+    }
+
+    /// Layout of the tab at the given display index: fullscreen wins over
+    /// visible floating terminal panes, anything else counts as tiled
+    fn tab_layout(&self, tab_position: usize) -> TabLayout {
+        let Some(tab) = self.tabs.get(tab_position) else {
+            return TabLayout::Tiled;
+        };
+        let has_floating_panes = self
+            .panes
+            .panes
+            .get(&tab.position)
+            .into_iter()
+            .flatten()
+            .any(|pane_info| pane_info.is_floating && !pane_info.is_plugin);
+
+        if tab.is_fullscreen_active {
+            TabLayout::Fullscreen
+        } else if tab.are_floating_panes_visible && has_floating_panes {
+            TabLayout::Floating
+        } else {
+            TabLayout::Tiled
+        }
+    }
+
+    /// The tab's focused terminal pane, falling back to its first terminal pane
+    fn active_pane(&self, tab_position: usize) -> Option<&PaneInfo> {
+        let terminal_panes: Vec<&PaneInfo> = self
+            .tabs
+            .get(tab_position)
+            .and_then(|tab| self.panes.panes.get(&tab.position))
+            .into_iter()
+            .flatten()
+            .filter(|pane_info| !pane_info.is_plugin)
+            .collect();
+
+        terminal_panes
+            .iter()
+            .find(|pane_info| pane_info.is_focused)
+            .or_else(|| terminal_panes.first())
+            .copied()
+    }
+
+    /// Title of the tab's active pane; empty if it has no terminal pane
+    fn active_pane_title(&self, tab_position: usize) -> String {
+        self.active_pane(tab_position)
+            .map(|pane_info| pane_info.title.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_tab_syncing(&self, tab_position: usize) -> bool {
+        self.tabs
+            .get(tab_position)
+            .is_some_and(|t| t.is_sync_panes_active)
+    }
+
+    /// 1-indexed position of the tab among the display indices tracked in
+    /// pane_to_tab, skipping gaps. None if the tab has no tracked panes.
+    fn visible_index(&self, tab_position: usize) -> Option<usize> {
+        let tracked_positions: BTreeSet<usize> = self.pane_to_tab.values().copied().collect();
+        if !tracked_positions.contains(&tab_position) {
+            return None;
+        }
+        Some(tracked_positions.range(..tab_position).count() + 1)
+    }
+
+    /// Current names of the tabs before and after the given display index,
+    /// empty at the boundaries
+    fn neighbor_tab_names(&self, tab_position: usize) -> (String, String) {
+        let name_at = |position: Option<usize>| {
+            position
+                .and_then(|position| self.tabs.get(position))
+                .map(|t| t.name.clone())
+                .unwrap_or_default()
+        };
+        (
+            name_at(tab_position.checked_sub(1)),
+            name_at(tab_position.checked_add(1)),
+        )
+    }
+
+    /// Hash of the neighboring tab names, used to detect when a format that
+    /// references them needs re-rendering
+    fn neighbor_names_hash(&self, tab_position: usize) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        self.neighbor_tab_names(tab_position).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Format tab name (wrapped in its prefix/suffix) with tab_position, cwd_basename,
+    /// active_marker, dup_index and group_index placeholders
+    fn format_tab_name(
+        &self,
+        tab_format: &TabFormat,
+        tab_position: usize,
+    ) -> Result<String, String> {
+        let uses_dup_index = tab_format.uses_placeholder("dup_index");
+        let uses_group_index = tab_format.uses_placeholder("group_index");
+        if !uses_dup_index && !uses_group_index {
+            return self.render_tab_name(tab_format, tab_position, "", "");
+        }
+
+        // Both indices are derived from the base name: the name rendered with
+        // an empty {dup_index} and {group_index}
+        let base_name = self.render_tab_name(tab_format, tab_position, "", "")?;
+        let earlier_base_names: Vec<Option<String>> = (0..tab_position)
+            .map(|position| self.base_tab_name_at(position))
+            .collect();
+
+        // Number this tab among earlier tabs sharing the same base name
+        let dup_index = if uses_dup_index {
+            let earlier_duplicates = earlier_base_names
+                .iter()
+                .filter(|name| name.as_ref() == Some(&base_name))
+                .count();
+            match earlier_duplicates {
+                0 if !tab_format.number_first_duplicate => String::new(),
+                n => (n + 1).to_string(),
+            }
+        } else {
+            String::new()
+        };
+
+        // Number this tab among earlier tabs whose name has the same group prefix
+        let delimiter = &tab_format.group_delimiter;
+        let group_index = match name_group(&base_name, delimiter) {
+            Some(group) if uses_group_index => {
+                let earlier_members = earlier_base_names
+                    .iter()
+                    .flatten()
+                    .filter(|name| name_group(name, delimiter) == Some(group))
+                    .count();
+                (earlier_members + 1).to_string()
+            }
+            _ => String::new(),
+        };
+
+        self.render_tab_name(tab_format, tab_position, &dup_index, &group_index)
+    }
+
+    /// The name sent to Zellij for a rendered name: wrapped in the global_prefix/
+    /// global_suffix config options, then cut to the format's max_columns
+    fn emitted_name(&self, tab_format: &TabFormat, name: &str) -> String {
+        let name = format!(
+            "{}{}{}",
+            self.config.global_prefix, name, self.config.global_suffix
+        );
+        match (tab_format.max_columns, tab_format.truncate_mode) {
+            (Some(max_columns), TruncateMode::Chars) => truncate_to_columns(&name, max_columns),
+            (Some(max_columns), TruncateMode::Segments) => {
+                truncate_to_segments(&name, max_columns, &tab_format.truncate_separator)
+            }
+            (None, _) => name,
+        }
+    }
+
+    /// The name of the tab at a display index with {dup_index} and {group_index} left empty.
+    /// Tabs without a stored format just use their current name.
+    fn base_tab_name_at(&self, tab_position: usize) -> Option<String> {
+        let stored_format = self
+            .stable_tab_id_at(tab_position)
+            .and_then(|stable_tab_id| self.stable_tab_id_to_format.get(&stable_tab_id));
+
+        match stored_format {
+            Some(tab_format) => self.render_tab_name(tab_format, tab_position, "", "").ok(),
+            None => self.tabs.get(tab_position).map(|t| t.name.clone()),
+        }
+    }
+
+    /// Last path component of the deepest directory containing the reported cwds
+    /// of all panes in the tab at tab_position. Empty if no pane reported one.
+    fn project_dir(&self, tab_position: usize) -> String {
+        let cwds = self
+            .pane_to_tab
+            .iter()
+            .filter(|(_, &position)| position == tab_position)
+            .filter_map(|(pane_id, _)| self.pane_to_cwd.get(pane_id))
+            .map(String::as_str);
+        common_directory(cwds)
+            .as_deref()
+            .map(cwd_basename)
+            .unwrap_or_default()
+    }
+
+    /// Set a timer for the start of the next day (UTC) if a stored format follows
+    /// the date and no such timer is pending yet
+    fn schedule_date_rollover(&mut self) {
+        if !self
+            .stable_tab_id_to_format
+            .values()
+            .any(TabFormat::follows_date)
+        {
+            return;
+        }
+        let Some(tomorrow) = current_date().and_then(|today| today.succ_opt()) else {
+            return;
+        };
+        if self.date_rollover == Some(tomorrow) {
+            return;
+        }
+
+        let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) else {
+            return;
+        };
+        // One second late, so the clock has surely moved on when the timer fires
+        let seconds_left = SECONDS_PER_DAY - now.as_secs() % SECONDS_PER_DAY + 1;
+        set_timeout(seconds_left as f64);
+        self.date_rollover = Some(tomorrow);
+    }
+
+    /// The first session_short_length characters of the session name, empty while
+    /// the name is not known yet
+    fn session_short(&self) -> String {
+        self.session_name
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .take(self.config.session_short_length)
+            .collect()
+    }
+
+    /// How many tabs from the right the tab at tab_position is: 1 (or 0 with
+    /// position_from_end_zero_based) for the last tab
+    fn tab_position_from_end(&self, tab_position: usize) -> usize {
+        let from_end = self.tabs.len().saturating_sub(tab_position);
+        if self.config.position_from_end_zero_based {
+            from_end.saturating_sub(1)
+        } else {
+            from_end
+        }
+    }
+
+    /// Render a tab format with all placeholders, using the given dup_index and group_index
+    fn render_tab_name(
+        &self,
+        tab_format: &TabFormat,
+        tab_position: usize,
+        dup_index: &str,
+        group_index: &str,
+    ) -> Result<String, String> {
+        use std::collections::HashMap;
+
+        // Custom vars first, so built-in placeholders override them
+        let mut vars: HashMap<String, String> = tab_format
+            .vars
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        // Create variables map with 1-indexed position
+        vars.insert("tab_position".to_string(), (tab_position + 1).to_string());
+        vars.insert("ordinal_word".to_string(), ordinal_word(tab_position + 1));
+        vars.insert("project_dir".to_string(), self.project_dir(tab_position));
+        vars.insert("session_short".to_string(), self.session_short());
+        let date = tab_format.render_date();
+        vars.insert(
+            "date".to_string(),
+            date.map_or_else(
+                || UNKNOWN_DATE.to_string(),
+                |date| date.format(&tab_format.date_format).to_string(),
+            ),
+        );
+        vars.insert(
+            "weekday".to_string(),
+            date.map_or_else(
+                || UNKNOWN_DATE.to_string(),
+                |date| date.format("%A").to_string(),
+            ),
+        );
+        vars.insert(
+            "tab_position_from_end".to_string(),
+            self.tab_position_from_end(tab_position).to_string(),
+        );
+        vars.insert(
+            "cwd_basename".to_string(),
+            tab_format
+                .cwd
+                .as_deref()
+                .map(cwd_basename)
+                .unwrap_or_default(),
+        );
+        let active_marker = if self.is_tab_active(tab_position) {
+            &tab_format.active_symbol
+        } else {
+            &tab_format.inactive_symbol
+        };
+        vars.insert("active_marker".to_string(), active_marker.clone());
+        let sync_marker = if self.is_tab_syncing(tab_position) {
+            &tab_format.sync_symbol
+        } else {
+            &tab_format.no_sync_symbol
+        };
+        vars.insert("sync_marker".to_string(), sync_marker.clone());
+        let layout_marker = match self.tab_layout(tab_position) {
+            TabLayout::Tiled => "",
+            TabLayout::Floating => &tab_format.floating_symbol,
+            TabLayout::Fullscreen => &tab_format.fullscreen_symbol,
+        };
+        vars.insert("layout_marker".to_string(), layout_marker.to_string());
+        vars.insert(
+            "active_pane_title".to_string(),
+            self.active_pane_title(tab_position),
+        );
+        // Zellij 0.40 tells plugins the command of command panes, but no process IDs
+        vars.insert("pane_pid".to_string(), String::new());
+        vars.insert(
+            "pane_args".to_string(),
+            self.active_pane(tab_position)
+                .and_then(|pane_info| pane_info.terminal_command.clone())
+                .unwrap_or_default(),
+        );
+        vars.insert("dup_index".to_string(), dup_index.to_string());
+        vars.insert("group_index".to_string(), group_index.to_string());
+        let age_minutes = self
+            .stable_tab_id_at(tab_position)
+            .and_then(|stable_tab_id| self.stable_tab_id_to_created_at.get(&stable_tab_id))
+            .map_or(0, |created_at| created_at.elapsed().as_secs() / 60);
+        vars.insert("age_minutes".to_string(), age_minutes.to_string());
+        let rename_count = self
+            .stable_tab_id_at(tab_position)
+            .and_then(|stable_tab_id| self.stable_tab_id_to_rename_count.get(&stable_tab_id))
+            .copied()
+            .unwrap_or(0);
+        vars.insert("rename_count".to_string(), rename_count.to_string());
+        vars.insert(
+            "pane_count".to_string(),
+            self.tab_pane_count(tab_position).to_string(),
+        );
+        vars.insert(
+            "pane_exit_code".to_string(),
+            self.tab_exit_code(tab_position)
+                .map(|code| code.to_string())
+                .unwrap_or_default(),
+        );
+        let (prev_tab_name, next_tab_name) = self.neighbor_tab_names(tab_position);
+        vars.insert("prev_tab_name".to_string(), prev_tab_name);
+        vars.insert("next_tab_name".to_string(), next_tab_name);
+        vars.insert(
+            "git_branch".to_string(),
+            tab_format
+                .cwd
+                .as_ref()
+                .and_then(|cwd| self.cwd_to_git_branch.get(cwd))
+                .cloned()
+                .unwrap_or_default(),
+        );
+        vars.insert(
+            "visible_index".to_string(),
+            self.visible_index(tab_position)
+                .map(|index| index.to_string())
+                .unwrap_or_default(),
+        );
+
+        let mut context = TemplateContext {
+            vars,
+            tab_position: tab_position as i64 + 1,
+        };
+        let prefix = render_template(&tab_format.prefix, &mut context)?;
+        let name = render_template(
+            tab_format.format_str_for(self.tab_pane_count(tab_position)),
+            &mut context,
+        )?;
+        let suffix = render_template(&tab_format.suffix, &mut context)?;
+
+        let name = format!("{}{}{}", prefix, name, suffix);
+        Ok(match tab_format.case {
+            Some(case) => case.apply(&name),
+            None => name,
+        })
+    }
+
+    /// Write a JSON response back to the CLI pipe that sent the message
+    fn respond(&self, pipe_message: &PipeMessage, response: &serde_json::Value) {
+        self.respond_text(pipe_message, &format!("{}\n", response));
+    }
+
+    /// Write raw text back to the CLI pipe that sent the message
+    fn respond_text(&self, pipe_message: &PipeMessage, text: &str) {
+        if let PipeSource::Cli(pipe_id) = &pipe_message.source {
+            cli_pipe_output(pipe_id, text);
+        }
+    }
+
+    /// Log an error message to stderr, unless the quiet config option is set
+    fn show_error(&self, message: &str) {
+        self.log(LogLevel::Error, || message.to_string());
+    }
+
+    /// Write a diagnostic to stderr if the configured log_level includes it.
+    /// The message is only built when it will be written.
+    fn log(&self, level: LogLevel, message: impl FnOnce() -> String) {
+        if level > self.config.log_level || (level <= LogLevel::Warn && self.config.quiet) {
+            return;
+        }
+        eprintln!("{}", message());
+    }
+}
+
+/// Read a boolean config key, treating anything other than "true" as false
+fn parse_bool(configuration: &BTreeMap<String, String>, key: &str) -> bool {
+    configuration.get(key).is_some_and(|v| v == "true")
+}
+
+/// Read a config key parsed with FromStr, falling back to the default when absent or invalid
+fn parse_value<T: FromStr>(
+    configuration: &BTreeMap<String, String>,
+    warnings: &mut Vec<String>,
+    key: &str,
+    default: T,
+) -> T {
+    match configuration.get(key) {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            warnings.push(format!(
+                "config: invalid value '{}' for {}, using default",
+                value, key
+            ));
+            default
+        }),
+        None => default,
+    }
+}
+
+/// Read the delim_open/delim_close config keys. Both must be set, non-empty and
+/// different; otherwise the default `{` `}` are kept.
+fn parse_delimiters(
+    configuration: &BTreeMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> Option<(String, String)> {
+    let open = configuration.get("delim_open");
+    let close = configuration.get("delim_close");
+    match (open, close) {
+        (None, None) => None,
+        (Some(open), Some(close)) if !open.is_empty() && !close.is_empty() && open != close => {
+            if open == "{" && close == "}" {
+                None
+            } else {
+                Some((open.clone(), close.clone()))
+            }
+        }
+        _ => {
+            warnings.push(
+                "config: delim_open and delim_close must both be set, non-empty and different, using { }"
+                    .to_string(),
+            );
+            None
+        }
+    }
+}
+
+/// Rewrite a template using custom delimiters into strfmt's `{}` syntax:
+/// delimiters become braces and literal braces are escaped
+fn translate_delimiters(template: &str, open: &str, close: &str) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix(open) {
+            out.push('{');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(close) {
+            out.push('}');
+            rest = after;
+        } else {
+            match c {
+                '{' => out.push_str("{{"),
+                '}' => out.push_str("}}"),
+                c => out.push(c),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// The group of a tab name: everything before the first delimiter, or None if
+/// the name has no (non-empty) group
+fn name_group<'a>(name: &'a str, delimiter: &str) -> Option<&'a str> {
+    if delimiter.is_empty() {
+        return None;
+    }
+    name.split_once(delimiter)
+        .map(|(group, _)| group)
+        .filter(|group| !group.is_empty())
+}
+
+/// How the tab ID of a rename was resolved, as reported in responses: "stable"
+/// for the Zellij #3535 workaround, "position" for tab.position + 1
+fn tab_id_mode(use_stable_ids: bool) -> &'static str {
+    if use_stable_ids {
+        "stable"
+    } else {
+        "position"
+    }
+}
+
+/// English ordinal for a 1-indexed position ("first", "second", ...), or just the
+/// number past "twentieth"
+fn ordinal_word(n: usize) -> String {
+    const WORDS: [&str; 20] = [
+        "first",
+        "second",
+        "third",
+        "fourth",
+        "fifth",
+        "sixth",
+        "seventh",
+        "eighth",
+        "ninth",
+        "tenth",
+        "eleventh",
+        "twelfth",
+        "thirteenth",
+        "fourteenth",
+        "fifteenth",
+        "sixteenth",
+        "seventeenth",
+        "eighteenth",
+        "nineteenth",
+        "twentieth",
+    ];
+    n.checked_sub(1)
+        .and_then(|index| WORDS.get(index))
+        .map_or_else(|| n.to_string(), |word| word.to_string())
+}
+
+/// Cut a name to at most max_columns terminal columns, counting wide characters
+/// (CJK, most emoji) as 2. Whole grapheme clusters are kept or dropped, so
+/// combining marks and emoji sequences are never split.
+fn truncate_to_columns(name: &str, max_columns: usize) -> String {
+    let mut columns = 0;
+    name.graphemes(true)
+        .take_while(|grapheme| {
+            columns += grapheme.width();
+            columns <= max_columns
+        })
+        .collect()
+}
+
+/// Shorten a name to at most max_columns terminal columns by replacing leading
+/// segments with `…`, e.g. `path/to/project` becomes `…/to/project` or `…/project`.
+/// Falls back to truncate_to_columns when the name has no separator or even its
+/// last segment doesn't fit.
+fn truncate_to_segments(name: &str, max_columns: usize, separator: &str) -> String {
+    if name.width() <= max_columns || separator.is_empty() {
+        return truncate_to_columns(name, max_columns);
+    }
+
+    let mut rest = name;
+    while let Some((_, tail)) = rest.split_once(separator) {
+        let shortened = format!("…{}{}", separator, tail);
+        if shortened.width() <= max_columns {
+            return shortened;
+        }
+        rest = tail;
+    }
+    truncate_to_columns(name, max_columns)
+}
+
+/// Whether a pane counts towards pane_to_tab and stable ID tracking
+fn is_tracked_pane(pane_info: &PaneInfo, include_suppressed: bool) -> bool {
+    !pane_info.is_plugin && (include_suppressed || !pane_info.is_suppressed)
+}
+
+/// Parse the JSON payload of a pipe command
+fn parse_payload<T: DeserializeOwned>(
+    command: &str,
+    payload: Option<String>,
+) -> Result<T, PipeError> {
+    let Some(payload) = payload else {
+        return Err(PipeError::new(
+            "MISSING_PAYLOAD",
+            format!("{}: missing payload", command),
+        ));
+    };
+
+    serde_json::from_str(&payload)
+        .map_err(|e| PipeError::new("INVALID_JSON", format!("{}: invalid JSON: {}", command, e)))
+}
+
+/// Length of an integer in a change-tab-name-bin frame header
+const BINARY_INT_LEN: usize = 5;
+
+/// Split a change-tab-name-bin payload into (pane_id, name) frames. Each frame is
+/// a pane id and the name's length in bytes, both as 5 bytes holding 7 bits each
+/// (most significant first), followed by the UTF-8 name. Header bytes stay below
+/// 0x80 so frames remain valid UTF-8, which pipe payloads must be.
+fn decode_binary_frames(payload: &[u8]) -> Result<Vec<(u32, &str)>, PipeError> {
+    let invalid = |message: String| {
+        PipeError::new(
+            "INVALID_BINARY",
+            format!("change-tab-name-bin: {}", message),
+        )
+    };
+    if payload.is_empty() {
+        return Err(invalid("empty payload".to_string()));
+    }
+
+    let read_int = |bytes: &[u8], offset: usize| -> Result<u32, PipeError> {
+        let bytes = bytes
+            .get(offset..offset + BINARY_INT_LEN)
+            .ok_or_else(|| invalid(format!("truncated header at byte {}", offset)))?;
+        let value = bytes.iter().try_fold(0u64, |value, &byte| {
+            (byte < 0x80).then_some(value << 7 | u64::from(byte))
+        });
+        value
+            .filter(|&value| value <= u64::from(u32::MAX))
+            .map(|value| value as u32)
+            .ok_or_else(|| invalid(format!("invalid integer at byte {}", offset)))
+    };
+
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset < payload.len() {
+        let pane_id = read_int(payload, offset)?;
+        let name_len = read_int(payload, offset + BINARY_INT_LEN)? as usize;
+        let name_start = offset + 2 * BINARY_INT_LEN;
+        let name = name_start
+            .checked_add(name_len)
+            .and_then(|name_end| payload.get(name_start..name_end))
+            .ok_or_else(|| invalid(format!("truncated name at byte {}", name_start)))?;
+        let name = std::str::from_utf8(name)
+            .map_err(|_| invalid(format!("name at byte {} is not UTF-8", name_start)))?;
+        frames.push((pane_id, name));
+        offset = name_start + name_len;
+    }
+    Ok(frames)
+}
+
+/// Decode a change-tab-name-b64 payload (standard base64, padding optional)
+/// into the JSON text it wraps
+fn decode_base64_payload(payload: Option<&str>) -> Result<String, PipeError> {
+    let invalid = |message: String| {
+        PipeError::new(
+            "INVALID_BASE64",
+            format!("change-tab-name-b64: {}", message),
+        )
+    };
+    let payload = payload.ok_or_else(|| invalid("missing payload".to_string()))?;
+
+    let engine = base64::engine::GeneralPurpose::new(
+        &base64::alphabet::STANDARD,
+        base64::engine::GeneralPurposeConfig::new()
+            .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+    );
+    let bytes = engine
+        .decode(payload.trim())
+        .map_err(|e| invalid(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|_| invalid("decoded payload is not UTF-8".to_string()))
+}
+
+/// Parse a change-tab-name payload, either JSON or `key=value;key=value` pairs.
+/// Payloads not starting with `{` are treated as key=value pairs.
+fn parse_rename_payload(payload: Option<String>) -> Result<RenamePayload, PipeError> {
+    let rename_payload = match payload {
+        Some(payload) if !payload.trim_start().starts_with('{') => parse_form_payload(&payload),
+        payload => parse_payload("change-tab-name", payload),
+    }?;
+    if rename_payload.color_rules.is_some() {
+        return Err(PipeError::new(
+            "UNSUPPORTED",
+            "change-tab-name: color_rules is not supported, Zellij tab names can't be colored",
+        ));
+    }
+    Ok(rename_payload)
+}
+
+/// Match text against a glob pattern anchored at both ends, where `*` matches
+/// any run of characters (including none) and `?` matches exactly one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text index it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character and retry
+                Some((star, star_t)) => {
+                    backtrack = Some((star, star_t + 1));
+                    p = star + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parse `key=value;key=value` pairs into a RenamePayload.
+/// Supports pane_id, name and use_stable_ids; values cannot contain `;`.
+fn parse_form_payload(payload: &str) -> Result<RenamePayload, PipeError> {
+    let invalid = |message: String| {
+        PipeError::new(
+            "INVALID_FORM",
+            format!(
+                "change-tab-name: payload is neither JSON nor key=value pairs: {}",
+                message
+            ),
+        )
+    };
+
+    let mut fields = serde_json::Map::new();
+    for pair in payload.trim().split(';').filter(|p| !p.trim().is_empty()) {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(invalid(format!("expected key=value, got '{}'", pair)));
+        };
+
+        let value = match key.trim() {
+            "pane_id" | "name" => serde_json::Value::String(value.to_string()),
+            "use_stable_ids" => match value.trim() {
+                "true" => serde_json::Value::Bool(true),
+                "false" => serde_json::Value::Bool(false),
+                _ => {
+                    return Err(invalid(format!(
+                        "use_stable_ids must be true or false, got '{}'",
+                        value
+                    )))
+                }
+            },
+            other => return Err(invalid(format!("unknown key '{}'", other))),
+        };
+        fields.insert(key.trim().to_string(), value);
+    }
+
+    serde_json::from_value(serde_json::Value::Object(fields)).map_err(|e| invalid(e.to_string()))
+}
+
+/// Parse a pane_id, which is sent as a string containing a number
+fn parse_pane_id(command: &str, pane_id: &str) -> Result<u32, PipeError> {
+    pane_id.parse().map_err(|_| {
+        PipeError::new(
+            "INVALID_PANE_ID",
+            format!("{}: pane_id must be a string containing a number", command),
+        )
+    })
+}
+
+/// Fail with POSITION_MISMATCH if the payload's expected_tab_position doesn't match
+/// where the pane was found
+fn check_expected_position(
+    rename_payload: &RenamePayload,
+    pane_id: u32,
+    tab_position: usize,
+) -> Result<(), PipeError> {
+    match rename_payload.expected_tab_position {
+        Some(expected) if expected != tab_position => Err(PipeError::new(
+            "POSITION_MISMATCH",
+            format!(
+                "change-tab-name: pane {} is in the tab at display index {}, not expected_tab_position {}",
+                pane_id, tab_position, expected
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Call `rewrite` with the contents of every `{...}` placeholder in a template and
+/// replace the placeholder with the returned text, or keep it when None is returned.
+/// `{{`/`}}` escapes are kept and unbalanced braces are left for strfmt to report.
+fn rewrite_placeholders(
+    template: &str,
+    mut rewrite: impl FnMut(&str) -> Result<Option<String>, String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..2]);
+            rest = &tail[2..];
+            continue;
+        }
+
+        let Some(end) = tail.find('}').filter(|_| tail.starts_with('{')) else {
+            out.push_str(&tail[..1]);
+            rest = &tail[1..];
+            continue;
+        };
+
+        match rewrite(&tail[1..end])? {
+            Some(replacement) => out.push_str(&replacement),
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Values a template is rendered with
+struct TemplateContext {
+    /// Placeholder values for strfmt; passes may add generated vars
+    vars: std::collections::HashMap<String, String>,
+    /// 1-indexed position of the tab, for arithmetic expressions
+    tab_position: i64,
+}
+
+/// A template syntax extension. It is called with the contents of every `{...}`
+/// placeholder and returns the text replacing it, or None to leave the placeholder
+/// to later passes and finally strfmt. Text that should appear literally must be
+/// escaped as `{{`/`}}`.
+type TemplatePass = fn(&str, &mut TemplateContext) -> Result<Option<String>, String>;
+
+/// Passes run in order over the whole template before strfmt substitutes the
+/// remaining `{name}` and `{name:spec}` placeholders. New template syntax goes here.
+const TEMPLATE_PASSES: &[TemplatePass] = &[check_spec_widths, expand_default, expand_arithmetic];
+
+/// Largest width or precision a format spec may ask for. strfmt and std pad to
+/// whatever is requested, so `{name:99999999999}` would exhaust memory, and the
+/// wasm plugin aborts instead of unwinding.
+const MAX_FORMAT_WIDTH: usize = 256;
+
+/// Render a template: run every pass in TEMPLATE_PASSES, then let strfmt handle
+/// plain substitution, validation and escaping
+fn render_template(template: &str, context: &mut TemplateContext) -> Result<String, String> {
+    let mut template = template.to_string();
+    for pass in TEMPLATE_PASSES {
+        template = rewrite_placeholders(&template, |placeholder| pass(placeholder, context))?;
+    }
+    strfmt::strfmt(&template, &context.vars).map_err(|e| e.to_string())
+}
+
+/// Reject a `{name:spec}` placeholder whose spec asks for a width or precision above
+/// MAX_FORMAT_WIDTH. The placeholder itself is left to later passes.
+fn check_spec_widths(
+    placeholder: &str,
+    _context: &mut TemplateContext,
+) -> Result<Option<String>, String> {
+    // The text after `:-` is a default value, not a spec
+    let Some((_, spec)) = placeholder
+        .split_once(':')
+        .filter(|(_, spec)| !spec.starts_with('-'))
+    else {
+        return Ok(None);
+    };
+
+    if spec
+        .split(|c: char| !c.is_ascii_digit())
+        .any(|digits| digits.len() > 3 || digits.parse().is_ok_and(|n: usize| n > MAX_FORMAT_WIDTH))
+    {
+        return Err(format!(
+            "width or precision in '{{{}}}' is above the maximum of {}",
+            placeholder, MAX_FORMAT_WIDTH
+        ));
+    }
+    Ok(None)
+}
+
+/// Replace a `{name:-default}` placeholder with the var's value, or with the default
+/// when the var is missing or empty. The result is escaped so strfmt keeps it as is.
+fn expand_default(
+    placeholder: &str,
+    context: &mut TemplateContext,
+) -> Result<Option<String>, String> {
+    let Some((key, default)) = placeholder.split_once(":-") else {
+        return Ok(None);
+    };
+
+    let value = context
+        .vars
+        .get(key)
+        .filter(|value| !value.is_empty())
+        .map_or(default, String::as_str);
+    Ok(Some(value.replace('{', "{{").replace('}', "}}")))
+}
+
+/// Rewrite a `{tab_position+N}`, `{tab_position-N}` or `{tab_position*N}` placeholder
+/// (optionally followed by a `:spec`) into a generated var holding the computed value,
+/// so strfmt can format it like any other placeholder.
+fn expand_arithmetic(
+    placeholder: &str,
+    context: &mut TemplateContext,
+) -> Result<Option<String>, String> {
+    let (key, spec) = match placeholder.split_once(':') {
+        Some((key, spec)) => (key, Some(spec)),
+        None => (placeholder, None),
+    };
+
+    let Some(expr) = key
+        .strip_prefix("tab_position")
+        .filter(|expr| expr.starts_with(['+', '-', '*']))
+    else {
+        return Ok(None);
+    };
+
+    let value = eval_arithmetic(context.tab_position, expr)
+        .ok_or_else(|| format!("invalid arithmetic expression '{}'", key))?;
+    let var = format!("__tab_position_expr_{}", context.vars.len());
+    context.vars.insert(var.clone(), value.to_string());
+
+    Ok(Some(match spec {
+        Some(spec) => format!("{{{}:{}}}", var, spec),
+        None => format!("{{{}}}", var),
+    }))
+}
+
+/// Evaluate `<op><integer>` against a value, e.g. "+10" or "*2".
+/// Returns None for malformed expressions or on overflow.
+fn eval_arithmetic(value: i64, expr: &str) -> Option<i64> {
+    let mut chars = expr.chars();
+    let op = chars.next()?;
+    let operand: i64 = chars.as_str().trim().parse().ok()?;
+
+    match op {
+        '+' => value.checked_add(operand),
+        '-' => value.checked_sub(operand),
+        '*' => value.checked_mul(operand),
+        _ => None,
+    }
+}
+
+/// Format a numeric string with a `[0][width][.precision]` spec: a leading `0`
+/// pads with zeros instead of spaces, width is the minimum length and precision
+/// the number of decimals. Without a precision the number is printed as parsed.
+fn format_number(value: &str, spec: &str) -> Result<String, String> {
+    let number: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+
+    let (width_spec, precision) = match spec.split_once('.') {
+        Some((width_spec, precision)) => (width_spec, Some(precision)),
+        None => (spec, None),
+    };
+    let (zero_pad, width) = match width_spec.strip_prefix('0') {
+        Some(width) => (true, width),
+        None => (false, width_spec),
+    };
+
+    let parse_digits = |digits: &str| -> Result<Option<usize>, String> {
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("malformed spec '{}'", spec));
+        }
+        match digits.parse() {
+            Ok(n) if n <= MAX_FORMAT_WIDTH => Ok(Some(n)),
+            Ok(_) | Err(_) => Err(format!(
+                "width or precision in '{}' is above the maximum of {}",
+                spec, MAX_FORMAT_WIDTH
+            )),
+        }
+    };
+    let width = parse_digits(width)?.unwrap_or(0);
+    let precision = match precision {
+        Some("") => return Err(format!("malformed spec '{}': missing precision", spec)),
+        Some(precision) => parse_digits(precision)?,
+        None => None,
+    };
+
+    Ok(match (zero_pad, precision) {
+        (true, Some(precision)) => format!("{:0width$.precision$}", number),
+        (true, None) => format!("{:0width$}", number),
+        (false, Some(precision)) => format!("{:width$.precision$}", number),
+        (false, None) => format!("{:width$}", number),
+    })
+}
+
+/// Quote a CSV field if it contains a comma, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Last path component of a working directory.
+/// Trailing slashes are ignored and the root directory is returned as "/".
+fn cwd_basename(cwd: &str) -> String {
+    let trimmed = cwd.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return if cwd.is_empty() {
+            String::new()
+        } else {
+            "/".to_string()
+        };
+    }
+    trimmed.rsplit('/').next().unwrap_or(trimmed).to_string()
+}
+
+/// The deepest directory that contains all of the given directories, None if
+/// there are none. Paths are compared by component, ignoring repeated and
+/// trailing slashes.
+fn common_directory<'a>(mut cwds: impl Iterator<Item = &'a str>) -> Option<String> {
+    let components = |cwd: &'a str| cwd.split('/').filter(|c| !c.is_empty());
+    let first = cwds.next()?;
+    let mut common: Vec<&str> = components(first).collect();
+    for cwd in cwds {
+        let shared = common
+            .iter()
+            .zip(components(cwd))
+            .take_while(|(a, b)| *a == b)
+            .count();
+        common.truncate(shared);
+    }
+    Some(format!("/{}", common.join("/")))
+}
+
+/// Today's date in UTC from the WASI clock, None if the clock is unavailable
+fn current_date() -> Option<chrono::NaiveDate> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?;
+    chrono::NaiveDateTime::from_timestamp_opt(now.as_secs() as i64, 0).map(|time| time.date())
+}
+
+/// Whether chrono can render a strftime format
+fn is_valid_date_format(format: &str) -> bool {
+    !chrono::format::StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error)
+}
+
+/// Branch checked out in the git repository containing `cwd`: the branch name,
+/// or the abbreviated commit hash for a detached HEAD. Handles worktrees and
+/// submodules whose `.git` is a file pointing at the real git directory.
+fn read_git_branch(cwd: &str) -> Option<String> {
+    use std::path::Path;
+
+    let mut dir = Some(Path::new(cwd));
+    while let Some(current) = dir {
+        let dot_git = current.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            Some(dot_git)
+        } else {
+            std::fs::read_to_string(&dot_git).ok().and_then(|contents| {
+                let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+                Some(current.join(git_dir))
+            })
+        };
+
+        if let Some(git_dir) = git_dir {
+            let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+            let head = head.trim();
+            return Some(match head.strip_prefix("ref: refs/heads/") {
+                Some(branch) => branch.to_string(),
+                None => head.chars().take(7).collect(),
+            });
+        }
+        dir = current.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// zellij-tile sends every plugin command through this wasm import. Host test
+    /// builds have no host to talk to, so commands are dropped.
+    #[no_mangle]
+    extern "C" fn host_run_plugin_command() {}
+
+    fn tab(position: usize, name: &str, active: bool) -> TabInfo {
+        TabInfo {
+            position,
+            name: name.to_string(),
+            active,
+            ..Default::default()
+        }
+    }
+
+    fn tabs(names: &[&str]) -> Vec<TabInfo> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(position, name)| tab(position, name, position == 0))
+            .collect()
+    }
+
+    fn terminal(id: u32) -> PaneInfo {
+        PaneInfo {
+            id,
+            ..Default::default()
+        }
+    }
+
+    fn plugin(id: u32) -> PaneInfo {
+        PaneInfo {
+            id,
+            is_plugin: true,
+            ..Default::default()
+        }
+    }
+
+    fn manifest(panes: Vec<(usize, Vec<PaneInfo>)>) -> PaneManifest {
+        PaneManifest {
+            panes: panes.into_iter().collect(),
+        }
+    }
+
+    /// A plugin that has seen the given panes and tabs, without rate limiting
+    fn state(tab_list: Vec<TabInfo>, panes: Vec<(usize, Vec<PaneInfo>)>) -> State {
+        let mut state = State::default();
+        state.config.rename_interval = Duration::ZERO;
+        state.update(Event::PaneUpdate(manifest(panes)));
+        state.update(Event::TabUpdate(tab_list));
+        state
+    }
+
+    /// Play Zellij: show the names the plugin sent and report the tabs again
+    fn apply_renames(state: &mut State) {
+        let mut tab_list = state.tabs.clone();
+        for (position, tab) in tab_list.iter_mut().enumerate() {
+            if let Some(name) = state
+                .stable_tab_id_at(position)
+                .and_then(|id| state.last_emitted_name(id))
+            {
+                tab.name = name.clone();
+            }
+        }
+        state.update(Event::TabUpdate(tab_list));
+    }
+
+    fn tab_names(state: &State) -> Vec<&str> {
+        state.tabs.iter().map(|tab| tab.name.as_str()).collect()
+    }
+
+    fn pipe(
+        state: &mut State,
+        command: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, PipeError> {
+        let payload = Some(payload.to_string());
+        match command {
+            "change-tab-name" => state.change_tab_name(payload),
+            "rename-focused-tab" => state.rename_focused_tab(payload),
+            "set-autoupdate-for" => state.set_auto_update_for(payload),
+            "reset-to-default" => state.reset_to_default(payload),
+            "swap-names" => state.swap_names(payload),
+            "rename-dynamic" => state.rename_dynamic(payload),
+            "rename-all" => state.rename_all(payload),
+            "set-stable-id" => state.set_stable_id(payload),
+            "shift-renumber" => state.shift_renumber(payload),
+            "cleanup" => state.cleanup(),
+            other => panic!("no test dispatch for {}", other),
+        }
+    }
+
+    fn error_code(result: Result<serde_json::Value, PipeError>) -> &'static str {
+        result.expect_err("command should fail").code
+    }
+
+    /// Encode one change-tab-name-bin frame
+    fn binary_frame(pane_id: u32, name: &str) -> Vec<u8> {
+        let int = |value: u32| {
+            (0..BINARY_INT_LEN)
+                .rev()
+                .map(move |i| ((value >> (7 * i)) & 0x7f) as u8)
+        };
+        int(pane_id)
+            .chain(int(name.len() as u32))
+            .chain(name.bytes())
+            .collect()
+    }
+
+    #[test]
+    fn pathological_format_specs_are_rejected() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        for name in ["{x:99999999999999999999}", "{x:>257}", "{x:.9999}"] {
+            let payload = json!({"tab_position": 0, "name": name, "vars": {"x": "1.5"}});
+            assert_eq!(
+                error_code(pipe(&mut state, "change-tab-name", payload)),
+                "INVALID_FORMAT"
+            );
+        }
+
+        let payload = json!({"tab_position": 0, "name": "{x:>256}", "vars": {"x": "a"}});
+        let name = pipe(&mut state, "change-tab-name", payload).unwrap()["name"].clone();
+        assert_eq!(name.as_str().map(str::len), Some(256));
+
+        // Digits in a default value are not a width
+        let payload = json!({"tab_position": 0, "name": "{missing:-99999}"});
+        let response = pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(response["name"], "99999");
+    }
+
+    #[test]
+    fn pathological_number_formats_and_json_are_rejected() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let payload = json!({
+            "tab_position": 0,
+            "name": "{x}",
+            "vars": {"x": "1"},
+            "number_format": {"x": "099999999999"},
+        });
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "INVALID_NUMBER_FORMAT"
+        );
+
+        let payload = json!({"tab_position": 0, "name": "{date}", "date_format": "%Q"});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "INVALID_FORMAT"
+        );
+
+        // Deep nesting is rejected by serde_json's recursion limit, or skipped
+        // in unknown fields, instead of overflowing the stack
+        let nested = format!("{}1{}", "[".repeat(100_000), "]".repeat(100_000));
+        let payload = format!(
+            r#"{{"tab_position": 0, "name": "ok", "extra": {}}}"#,
+            nested
+        );
+        assert_eq!(state.change_tab_name(Some(payload)).unwrap()["name"], "ok");
+        let payload = format!(r#"{{"tab_position": 0, "name": {}}}"#, nested);
+        assert_eq!(
+            error_code(state.change_tab_name(Some(payload))),
+            "INVALID_JSON"
+        );
+        assert_eq!(
+            error_code(state.change_tab_name(Some("{\"name\": \"\\ud800\"}".to_string()))),
+            "INVALID_JSON"
+        );
+    }
+
+    #[test]
+    fn plugin_only_tabs_get_stable_ids_in_display_order() {
+        let mut state = state(
+            tabs(&["plugin", "a", "b"]),
+            vec![
+                (0, vec![plugin(1)]),
+                (1, vec![terminal(1)]),
+                (2, vec![terminal(2)]),
+            ],
+        );
+        let ids: Vec<_> = (0..3).map(|p| state.stable_tab_id_at(p)).collect();
+        assert_eq!(ids, vec![Some(1), Some(2), Some(3)]);
+
+        let payload = json!({"tab_position": 0, "name": "{tab_position}"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert!(state.stable_tab_id_to_format.contains_key(&1));
+
+        // Opening a terminal in the plugin-only tab keeps its ID and format
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![plugin(1), terminal(3)]),
+            (1, vec![terminal(1)]),
+            (2, vec![terminal(2)]),
+        ])));
+        state.update(Event::TabUpdate(state.tabs.clone()));
+        assert_eq!(state.pane_to_stable_tab_id.get(&3), Some(&1));
+        assert!(state.paneless_tab_to_stable_id.is_empty());
+        assert!(state.stable_tab_id_to_format.contains_key(&1));
+    }
+
+    #[test]
+    fn denied_permission_fails_every_rename_pipe() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        state.permission_status = Some(PermissionStatus::Denied);
+        for (command, payload) in [
+            ("change-tab-name", json!({"tab_position": 0, "name": "x"})),
+            ("reset-to-default", json!({"tab_position": 0})),
+            (
+                "swap-names",
+                json!({"first": {"tab_position": 0}, "second": {"tab_position": 1}}),
+            ),
+            ("cleanup", json!({})),
+        ] {
+            assert_eq!(
+                error_code(pipe(&mut state, command, payload)),
+                "PERMISSION_DENIED"
+            );
+        }
+    }
+
+    #[test]
+    fn git_branch_updates_respect_pause_and_pins() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        for tab_position in 0..2 {
+            let payload = json!({"tab_position": tab_position, "name": "[{git_branch}]", "cwd": "/host/repo"});
+            pipe(&mut state, "change-tab-name", payload).unwrap();
+        }
+        state.pinned_stable_tab_ids.insert(2);
+
+        let branch = |branch: &str| GitBranchResult {
+            cwd: "/host/repo".to_string(),
+            branch: branch.to_string(),
+        };
+        state.update_git_branch(branch("main"));
+        assert_eq!(
+            state.last_emitted_name(1).map(String::as_str),
+            Some("[main]")
+        );
+        assert_eq!(state.last_emitted_name(2).map(String::as_str), Some("[]"));
+
+        state.auto_update_paused = true;
+        state.update_git_branch(branch("dev"));
+        assert_eq!(
+            state.last_emitted_name(1).map(String::as_str),
+            Some("[main]")
+        );
+    }
+
+    #[test]
+    fn config_problems_are_returned_as_warnings() {
+        let configuration: BTreeMap<String, String> = [
+            ("log_level", "warn"),
+            ("rename_interval_ms", "soon"),
+            ("delim_open", "<<"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let (config, warnings) = Config::from_configuration(&configuration);
+        assert_eq!(config.log_level, LogLevel::Warn);
+        assert_eq!(config.rename_interval, Duration::from_millis(100));
+        assert_eq!(config.delimiters, None);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("rename_interval_ms"));
+        assert!(warnings[1].contains("delim_open"));
+    }
+
+    #[test]
+    fn external_rename_stops_auto_update() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        let payload = json!({"tab_position": 1, "name": "{tab_position}: b"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["a", "2: b"]);
+        assert!(state.stable_tab_id_to_format.contains_key(&2));
+
+        let mut renamed = state.tabs.clone();
+        renamed[1].name = "mine".to_string();
+        state.update(Event::TabUpdate(renamed));
+        assert!(!state.stable_tab_id_to_format.contains_key(&2));
+
+        // Closing the first tab would otherwise re-render it as "1: b"
+        state.update(Event::PaneUpdate(manifest(vec![(0, vec![terminal(2)])])));
+        state.update(Event::TabUpdate(vec![tab(0, "mine", true)]));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["mine"]);
+    }
+
+    #[test]
+    fn moving_a_renamed_tab_is_not_an_external_rename() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        let payload = json!({"tab_position": 1, "name": "{tab_position}: b"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+
+        // Swap the tabs, with the panes reported before the tabs
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![terminal(2)]),
+            (1, vec![terminal(1)]),
+        ])));
+        state.update(Event::TabUpdate(vec![
+            tab(0, "2: b", true),
+            tab(1, "a", false),
+        ]));
+        assert!(state.stable_tab_id_to_format.contains_key(&2));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["1: b", "a"]);
+    }
+
+    #[test]
+    fn dump_state_keeps_every_key_at_the_top_level() {
+        let state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let dump = state.dump_state();
+        for key in [
+            "ok",
+            "config",
+            "tabs",
+            "pane_to_stable_tab_id",
+            "stable_tab_id_to_format",
+            "stable_tab_id_to_previous_name",
+            "template_file_cache",
+        ] {
+            assert!(dump.get(key).is_some(), "missing {}", key);
+        }
+        assert_eq!(dump["config"]["rename_interval_ms"], 0);
+        assert_eq!(dump["pane_to_stable_tab_id"]["1"], 1);
+    }
+
+    #[test]
+    fn menu_closes_after_a_rename_or_esc() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        state.open_menu();
+        for key in "2x".chars().map(Key::Char).chain([Key::Char('\n')]) {
+            state.handle_menu_key(key);
+        }
+        assert!(state.menu.is_none());
+        assert_eq!(state.last_emitted_name(2).map(String::as_str), Some("x"));
+
+        state.open_menu();
+        state.handle_menu_key(Key::Esc);
+        assert!(state.menu.is_none());
+    }
+
+    #[test]
+    fn duplicate_pane_ids_keep_the_leftmost_tab() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2), terminal(1)])],
+        );
+        assert_eq!(state.pane_to_tab.get(&1), Some(&0));
+        assert_eq!(state.pane_to_tab.get(&2), Some(&1));
+        assert_eq!(state.stable_tab_id_at(0), Some(1));
+        assert_eq!(state.stable_tab_id_at(1), Some(2));
+
+        // Renaming through the duplicated pane targets the leftmost tab
+        let payload = json!({"pane_id": "1", "name": "x"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(state.last_emitted_name(1).map(String::as_str), Some("x"));
+        assert_eq!(state.last_emitted_name(2), None);
+    }
+
+    #[test]
+    fn frozen_names_are_not_re_rendered_after_a_reorder() {
+        let mut state = state(
+            tabs(&["a", "b", "c"]),
+            vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(3)]),
+            ],
+        );
+        let payload = json!({"tab_position": 1, "name": "{tab_position}: b"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        let payload =
+            json!({"tab_position": 2, "name": "{tab_position}: c", "freeze_after_apply": true});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["a", "2: b", "3: c"]);
+
+        // Move the last tab to the front
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![terminal(3)]),
+            (1, vec![terminal(1)]),
+            (2, vec![terminal(2)]),
+        ])));
+        state.update(Event::TabUpdate(vec![
+            tab(0, "3: c", true),
+            tab(1, "a", false),
+            tab(2, "2: b", false),
+        ]));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["3: c", "a", "3: b"]);
+
+        // The frozen rename is still in the tab's history
+        assert!(state.stable_tab_id_to_history[&3]
+            .iter()
+            .any(|(name, _)| name == "3: c"));
+    }
+
+    #[test]
+    fn bulk_reorder_keeps_stable_ids_with_their_panes() {
+        let panes = |order: &[u32]| {
+            order
+                .iter()
+                .enumerate()
+                .map(|(position, &first)| (position, vec![terminal(first), terminal(first + 1)]))
+                .collect::<Vec<_>>()
+        };
+        let mut state = state(tabs(&["a", "b", "c", "d"]), panes(&[10, 20, 30, 40]));
+        let ids: Vec<_> = [10, 20, 30, 40]
+            .iter()
+            .map(|pane_id| state.pane_to_stable_tab_id[pane_id])
+            .collect();
+
+        // Reverse the tabs, once with the panes reported first and once with the tabs
+        state.update(Event::PaneUpdate(manifest(panes(&[40, 30, 20, 10]))));
+        state.update(Event::TabUpdate(tabs(&["d", "c", "b", "a"])));
+        state.update(Event::TabUpdate(tabs(&["a", "b", "c", "d"])));
+        state.update(Event::PaneUpdate(manifest(panes(&[10, 20, 30, 40]))));
+        state.update(Event::TabUpdate(tabs(&["d", "a", "c", "b"])));
+        state.update(Event::PaneUpdate(manifest(panes(&[40, 10, 30, 20]))));
+
+        for (pane_id, &stable_tab_id) in [10, 20, 30, 40].iter().zip(&ids) {
+            assert_eq!(state.pane_to_stable_tab_id[pane_id], stable_tab_id);
+            assert_eq!(state.pane_to_stable_tab_id[&(pane_id + 1)], stable_tab_id);
+        }
+        assert_eq!(state.stable_tab_id_at(0), Some(ids[3]));
+        assert_eq!(state.stable_tab_id_at(1), Some(ids[0]));
+    }
+
+    #[test]
+    fn plugin_pane_ids_resolve_only_with_allow_plugin_pane() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2), plugin(1)])],
+        );
+        // Plugin pane 1 is a different pane than terminal pane 1
+        let payload = json!({"pane_id": "1", "name": "plugin", "allow_plugin_pane": true});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(
+            state.last_emitted_name(2).map(String::as_str),
+            Some("plugin")
+        );
+        assert_eq!(state.last_emitted_name(1), None);
+
+        let payload = json!({"pane_id": "1", "name": "terminal"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(
+            state.last_emitted_name(1).map(String::as_str),
+            Some("terminal")
+        );
+
+        let payload = json!({"pane_id": "2", "name": "x", "allow_plugin_pane": true});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "PANE_NOT_FOUND"
+        );
+
+        // Plugin panes never get stable IDs of their own
+        assert_eq!(state.pane_to_stable_tab_id.len(), 2);
+    }
+
+    #[test]
+    fn rename_focused_tab_follows_the_focused_terminal_pane() {
+        let focused = |mut pane: PaneInfo| {
+            pane.is_focused = true;
+            pane
+        };
+        // Focus is on a plugin pane in the active tab, and on a terminal in the next tab
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![
+                (0, vec![terminal(1), focused(plugin(5))]),
+                (1, vec![focused(terminal(2))]),
+            ],
+        );
+        pipe(&mut state, "rename-focused-tab", json!({"name": "x"})).unwrap();
+        assert_eq!(state.last_emitted_name(2).map(String::as_str), Some("x"));
+
+        // The active tab's focused terminal pane wins over other tabs'
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![focused(terminal(1))]),
+            (1, vec![focused(terminal(2))]),
+        ])));
+        pipe(&mut state, "rename-focused-tab", json!({"name": "y"})).unwrap();
+        assert_eq!(state.last_emitted_name(1).map(String::as_str), Some("y"));
+    }
+
+    #[test]
+    fn denied_permission_event_fails_renames() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        state.update(Event::PermissionRequestResult(PermissionStatus::Denied));
+        let payload = json!({"pane_id": "1", "name": "x"});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload.clone())),
+            "PERMISSION_DENIED"
+        );
+        assert_eq!(state.last_emitted_name(1), None);
+
+        state.update(Event::PermissionRequestResult(PermissionStatus::Granted));
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(state.last_emitted_name(1).map(String::as_str), Some("x"));
+    }
+
+    #[test]
+    fn placeholder_defaults_cover_empty_and_missing_values() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let mut render = |name: &str, vars: serde_json::Value| {
+            let payload = json!({"tab_position": 0, "name": name, "vars": vars});
+            pipe(&mut state, "change-tab-name", payload).unwrap()["name"].clone()
+        };
+        assert_eq!(render("{branch:-main}", json!({"branch": "dev"})), "dev");
+        assert_eq!(render("{branch:-main}", json!({"branch": ""})), "main");
+        assert_eq!(render("{branch:-main}", json!({})), "main");
+        assert_eq!(render("[{branch:-}]", json!({})), "[]");
+        // Built-in placeholders take defaults too
+        assert_eq!(render("{pane_title:-shell}", json!({})), "shell");
+    }
+
+    #[test]
+    fn stable_ids_restart_from_one_once_every_tab_closed() {
+        // The ID the tab opened after closing every tab gets
+        let id_after_closing_all = |reset_ids_when_empty: bool| {
+            let mut state = state(
+                tabs(&["a", "b", "c"]),
+                vec![
+                    (0, vec![terminal(1)]),
+                    (1, vec![terminal(2)]),
+                    (2, vec![terminal(3)]),
+                ],
+            );
+            state.config.reset_ids_when_empty = reset_ids_when_empty;
+            assert_eq!(state.stable_tab_id_at(2), Some(3));
+
+            // Closing the last tab frees its ID while others are open
+            state.update(Event::PaneUpdate(manifest(vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+            ])));
+            state.update(Event::TabUpdate(tabs(&["a", "b"])));
+            state.update(Event::PaneUpdate(manifest(vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(4)]),
+            ])));
+            state.update(Event::TabUpdate(tabs(&["a", "b", "c"])));
+            assert_eq!(state.stable_tab_id_at(2), Some(3));
+
+            state.update(Event::PaneUpdate(manifest(vec![])));
+            state.update(Event::TabUpdate(vec![]));
+            assert!(state.pane_to_stable_tab_id.is_empty());
+
+            state.update(Event::PaneUpdate(manifest(vec![(0, vec![terminal(5)])])));
+            state.update(Event::TabUpdate(tabs(&["d"])));
+            state.stable_tab_id_at(0)
+        };
+
+        assert_eq!(id_after_closing_all(true), Some(1));
+        assert_eq!(id_after_closing_all(false), Some(4));
+    }
+
+    #[test]
+    fn unique_renames_error_or_pick_the_smallest_free_suffix() {
+        let mut state = state(
+            tabs(&["dev", "dev 2", "c", "d"]),
+            vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(3)]),
+                (3, vec![terminal(4)]),
+            ],
+        );
+        let payload = json!({"tab_position": 2, "name": "dev", "unique": true});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "NAME_COLLISION"
+        );
+
+        let payload =
+            json!({"tab_position": 2, "name": "dev", "unique": true, "on_collision": "suffix"});
+        assert_eq!(
+            pipe(&mut state, "change-tab-name", payload).unwrap()["name"],
+            "dev 3"
+        );
+
+        // The tab's own name doesn't count as a collision
+        let payload = json!({"tab_position": 0, "name": "dev", "unique": true});
+        assert_eq!(
+            pipe(&mut state, "change-tab-name", payload).unwrap()["name"],
+            "dev"
+        );
+
+        // Without unique, duplicates are allowed
+        let payload = json!({"tab_position": 3, "name": "dev"});
+        assert_eq!(
+            pipe(&mut state, "change-tab-name", payload).unwrap()["name"],
+            "dev"
+        );
+    }
+
+    #[test]
+    fn stale_mappings_are_rebuilt_or_reported() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        // Tabs shrank without a rebuild, so pane 2 still maps past the last tab
+        state.tabs.truncate(1);
+        let rename_payload: RenamePayload = serde_json::from_value(json!({"name": "x"})).unwrap();
+        assert_eq!(
+            error_code(state.rename_target(rename_payload, (1, Some(2)))),
+            "STALE_MAPPING"
+        );
+
+        // pipe() revalidates first, so the pane is simply gone
+        state.revalidate_mappings();
+        assert_eq!(state.pane_to_tab.get(&2), None);
+        let payload = json!({"pane_id": "2", "name": "x"});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "PANE_NOT_FOUND"
+        );
+    }
+
+    #[test]
+    fn pane_count_templates_follow_pane_count_changes() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        let payload = json!({
+            "tab_position": 0,
+            "name": "fallback",
+            "templates": {"1": "editor", ">1": "editor ({pane_count})"},
+        });
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        let payload = json!({"tab_position": 1, "name": "fallback", "templates": {"1": "one"}});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["editor", "one"]);
+
+        let set_panes = |state: &mut State, panes: Vec<(usize, Vec<PaneInfo>)>| {
+            state.update(Event::PaneUpdate(manifest(panes)));
+            apply_renames(state);
+        };
+        set_panes(
+            &mut state,
+            vec![
+                (0, vec![terminal(1), terminal(3)]),
+                (1, vec![terminal(2), terminal(4)]),
+            ],
+        );
+        assert_eq!(tab_names(&state), vec!["editor (2)", "fallback"]);
+
+        set_panes(
+            &mut state,
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        assert_eq!(tab_names(&state), vec!["editor", "one"]);
+    }
+
+    #[test]
+    fn moved_tab_is_renamed_even_if_its_new_position_shows_the_target_name() {
+        let mut state = state(
+            tabs(&["1", "b", "c"]),
+            vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(3)]),
+            ],
+        );
+        let payload = json!({"tab_position": 1, "name": "{tab_position}"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["1", "2", "c"]);
+
+        // Close the first tab, with the panes reported before the tabs: until the
+        // TabUpdate, the tab's new position still shows the closed tab's "1"
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![terminal(2)]),
+            (1, vec![terminal(3)]),
+        ])));
+        state.update(Event::TabUpdate(vec![
+            tab(0, "2", true),
+            tab(1, "c", false),
+        ]));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["1", "c"]);
+    }
+
+    #[test]
+    fn auto_update_can_be_disabled_for_one_tab() {
+        let mut state = state(
+            tabs(&["a", "b", "c"]),
+            vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(3)]),
+            ],
+        );
+        for tab_position in 1..3 {
+            let payload = json!({"tab_position": tab_position, "name": "{tab_position}"});
+            pipe(&mut state, "change-tab-name", payload).unwrap();
+        }
+        let payload = json!({"pane_id": "2", "enabled": false});
+        let response = pipe(&mut state, "set-autoupdate-for", payload).unwrap();
+        assert_eq!(response["stable_id"], 2);
+        apply_renames(&mut state);
+
+        // Close the first tab: only the tab still auto-updating is renumbered
+        let close_first = |state: &mut State| {
+            state.update(Event::PaneUpdate(manifest(vec![
+                (0, vec![terminal(2)]),
+                (1, vec![terminal(3)]),
+            ])));
+            let mut tab_list = state.tabs[1..].to_vec();
+            for (position, tab) in tab_list.iter_mut().enumerate() {
+                tab.position = position;
+            }
+            state.update(Event::TabUpdate(tab_list));
+            apply_renames(state);
+        };
+        close_first(&mut state);
+        assert_eq!(tab_names(&state), vec!["2", "2"]);
+
+        // A rename stores the new format but keeps the tab frozen
+        let payload = json!({"pane_id": "2", "name": "{tab_position}!"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["1!", "2"]);
+        assert!(state.disabled_autoupdate.contains(&2));
+
+        // Turning it back on re-evaluates the tab right away
+        let payload = json!({"pane_id": "2", "enabled": true});
+        pipe(&mut state, "set-autoupdate-for", payload).unwrap();
+        assert!(!state.disabled_autoupdate.contains(&2));
+    }
+
+    #[test]
+    fn state_left_behind_by_a_closed_tab_is_eventually_reclaimed() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        state.config.stale_id_gc_rebuilds = 3;
+
+        // Pane 2 goes away, but some of its tab's state is left behind
+        state.update(Event::PaneUpdate(manifest(vec![(0, vec![terminal(1)])])));
+        state.update(Event::TabUpdate(tabs(&["a"])));
+        assert!(!state.pane_to_stable_tab_id.contains_key(&2));
+        state
+            .stable_tab_id_to_format
+            .insert(2, TabFormat::literal("b"));
+        state.pinned_stable_tab_ids.insert(2);
+
+        for _ in 0..3 {
+            state.rebuild_pane_to_tab();
+        }
+        assert!(state.stable_tab_id_to_format.contains_key(&2));
+
+        state.rebuild_pane_to_tab();
+        assert!(!state.stable_tab_id_to_format.contains_key(&2));
+        assert!(!state.pinned_stable_tab_ids.contains(&2));
+        assert!(state.stable_tab_id_to_missing_rebuilds.is_empty());
+    }
+
+    #[test]
+    fn auto_update_does_not_re_emit_unchanged_names() {
+        let mut state = state(
+            tabs(&["a", "b", "c"]),
+            vec![
+                (0, vec![terminal(1)]),
+                (1, vec![terminal(2)]),
+                (2, vec![terminal(3)]),
+            ],
+        );
+        let payload = json!({"tab_position": 2, "name": "{tab_position}: c"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        let payload = json!({"tab_position": 1, "name": "{pane_count} pane"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        let renames = state.metrics.renames_applied;
+
+        // Redundant updates, before and after Zellij shows the names
+        state.rebuild_pane_to_tab();
+        state.update(Event::TabUpdate(state.tabs.clone()));
+        assert_eq!(state.metrics.renames_applied, renames);
+
+        // Close the first tab: "{pane_count} pane" renders the same name at its new
+        // position, so only the other tab is renamed
+        state.update(Event::PaneUpdate(manifest(vec![
+            (0, vec![terminal(2)]),
+            (1, vec![terminal(3)]),
+        ])));
+        // Auto-update already sent "2: c", which the next rebuild must not repeat
+        state.rebuild_pane_to_tab();
+        state.update(Event::TabUpdate(vec![
+            tab(0, "1 pane", true),
+            tab(1, "3: c", false),
+        ]));
+        assert_eq!(state.metrics.renames_applied, renames + 1);
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), vec!["1 pane", "2: c"]);
+        assert_eq!(state.metrics.renames_applied, renames + 1);
+    }
+
+    fn render(template: &str, vars: &[(&str, &str)], tab_position: i64) -> Result<String, String> {
+        let mut context = TemplateContext {
+            vars: vars
+                .iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            tab_position,
+        };
+        render_template(template, &mut context)
+    }
+
+    #[test]
+    fn template_engine_substitutes_like_strfmt() {
+        let vars = [("a", "x"), ("b", "yz")];
+        assert_eq!(render("{a}-{b}", &vars, 1).unwrap(), "x-yz");
+        assert_eq!(render("plain", &vars, 1).unwrap(), "plain");
+        assert_eq!(render("{{a}} {a}", &vars, 1).unwrap(), "{a} x");
+        assert_eq!(render("[{b:>4}]", &vars, 1).unwrap(), "[  yz]");
+        assert_eq!(render("[{b:<4}]", &vars, 1).unwrap(), "[yz  ]");
+        assert_eq!(render("[{b:^4}]", &vars, 1).unwrap(), "[ yz ]");
+
+        // Errors are strfmt's own
+        for template in ["{missing}", "{a", "a}", "{a:q}"] {
+            let expected = strfmt::strfmt(template, &render_vars(&vars)).unwrap_err();
+            assert_eq!(
+                render(template, &vars, 1).unwrap_err(),
+                expected.to_string()
+            );
+        }
+    }
+
+    fn render_vars(vars: &[(&str, &str)]) -> std::collections::HashMap<String, String> {
+        vars.iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn template_engine_expands_defaults() {
+        let vars = [("a", "x"), ("empty", "")];
+        assert_eq!(render("{a:-d}", &vars, 1).unwrap(), "x");
+        assert_eq!(render("{empty:-d}", &vars, 1).unwrap(), "d");
+        assert_eq!(
+            render("{missing:-no branch}", &vars, 1).unwrap(),
+            "no branch"
+        );
+        assert_eq!(render("{missing:-}", &vars, 1).unwrap(), "");
+        // Values are literal text, not templates
+        let vars = [("braces", "{a}")];
+        assert_eq!(render("{braces:-d}", &vars, 1).unwrap(), "{a}");
+    }
+
+    #[test]
+    fn template_engine_evaluates_tab_position_arithmetic() {
+        assert_eq!(render("{tab_position+10}", &[], 3).unwrap(), "13");
+        assert_eq!(render("{tab_position-5}", &[], 3).unwrap(), "-2");
+        assert_eq!(render("{tab_position*2:>3}", &[], 3).unwrap(), "  6");
+        assert_eq!(
+            render("{tab_position+1}/{tab_position+2}", &[], 1).unwrap(),
+            "2/3"
+        );
+        for template in [
+            "{tab_position+x}",
+            "{tab_position/2}",
+            "{tab_position*9223372036854775807}",
+        ] {
+            assert!(render(template, &[], 3).is_err(), "{}", template);
+        }
+        assert_eq!(
+            render("{tab_position+x}", &[], 3).unwrap_err(),
+            "invalid arithmetic expression 'tab_position+x'"
+        );
+        // Plain {tab_position} is a var like any other
+        assert_eq!(
+            render("{tab_position}", &[("tab_position", "3")], 3).unwrap(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn template_engine_passes_compose() {
+        let vars = [("a", "x")];
+        assert_eq!(
+            render("{a:-d} {missing:-d} #{tab_position+1:<2}", &vars, 4).unwrap(),
+            "x d #5 "
+        );
+        // A default doesn't run through the later passes
+        assert_eq!(
+            render("{missing:-tab_position+1}", &vars, 4).unwrap(),
+            "tab_position+1"
+        );
+    }
+
+    #[test]
+    fn rewrite_placeholders_keeps_escapes_and_unhandled_placeholders() {
+        let upper =
+            |placeholder: &str| Ok((placeholder != "keep").then(|| placeholder.to_uppercase()));
+        assert_eq!(
+            rewrite_placeholders("x{{y}}{z}{keep}", upper).unwrap(),
+            "x{{y}}Z{keep}"
+        );
+        assert_eq!(rewrite_placeholders("{a", upper).unwrap(), "{a");
+        assert_eq!(rewrite_placeholders("a}b", upper).unwrap(), "a}b");
+        assert_eq!(
+            rewrite_placeholders("{a}", |_| Err("boom".to_string())).unwrap_err(),
+            "boom"
+        );
+    }
+
+    #[test]
+    fn eval_arithmetic_rejects_malformed_expressions_and_overflow() {
+        assert_eq!(eval_arithmetic(3, "+10"), Some(13));
+        assert_eq!(eval_arithmetic(3, "- 1"), Some(2));
+        assert_eq!(eval_arithmetic(3, "*-2"), Some(-6));
+        assert_eq!(eval_arithmetic(3, ""), None);
+        assert_eq!(eval_arithmetic(3, "+"), None);
+        assert_eq!(eval_arithmetic(3, "/2"), None);
+        assert_eq!(eval_arithmetic(i64::MAX, "+1"), None);
+    }
+
+    #[test]
+    fn stable_ids_shared_by_two_tabs_are_split() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2), terminal(3)])],
+        );
+        let payload = json!({"tab_position": 1, "name": "{tab_position}: b"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+
+        // Both of tab 1's panes and tab 0's pane claim stable ID 2
+        state.pane_to_stable_tab_id.insert(1, 2);
+        state.rebuild_pane_to_tab();
+
+        // Tab 1 has more panes with the ID, so it keeps it and its format
+        assert_eq!(state.stable_tab_id_at(1), Some(2));
+        assert!(state.stable_tab_id_to_format.contains_key(&2));
+        let split_id = state.stable_tab_id_at(0).unwrap();
+        assert_eq!(split_id, 3);
+        assert_eq!(state.pane_to_stable_tab_id[&1], split_id);
+        assert!(!state.stable_tab_id_to_format.contains_key(&split_id));
+    }
+
+    #[test]
+    fn expected_tab_position_guards_pane_renames() {
+        let mut state = state(
+            tabs(&["a", "b"]),
+            vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])],
+        );
+        let payload = json!({"pane_id": "2", "name": "x", "expected_tab_position": 1});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(state.last_emitted_name(2).map(String::as_str), Some("x"));
+
+        let payload = json!({"pane_id": "2", "name": "y", "expected_tab_position": 0});
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "POSITION_MISMATCH"
+        );
+        assert_eq!(state.last_emitted_name(2).map(String::as_str), Some("x"));
+    }
+
+    #[test]
+    fn template_length_is_capped_at_the_boundary() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let rename =
+            |state: &mut State, payload: serde_json::Value| pipe(state, "change-tab-name", payload);
+        let long = |n: usize| "é".repeat(n);
+
+        rename(&mut state, json!({"tab_position": 0, "name": long(512)})).unwrap();
+        let payload = json!({"tab_position": 0, "name": long(513)});
+        assert_eq!(error_code(rename(&mut state, payload)), "TEMPLATE_TOO_LONG");
+
+        // Prefix, suffix and pane count templates count towards the limit
+        let payload = json!({"tab_position": 0, "name": long(510), "prefix": "<", "suffix": ">"});
+        rename(&mut state, payload).unwrap();
+        let payload = json!({"tab_position": 0, "name": long(511), "prefix": "<", "suffix": ">"});
+        assert_eq!(error_code(rename(&mut state, payload)), "TEMPLATE_TOO_LONG");
+        let payload = json!({"tab_position": 0, "name": "a", "templates": {">0": long(513)}});
+        assert_eq!(error_code(rename(&mut state, payload)), "TEMPLATE_TOO_LONG");
+
+        state.config.max_template_length = 0;
+        rename(&mut state, json!({"tab_position": 0, "name": long(2000)})).unwrap();
+    }
+
+    #[test]
+    fn placeholder_values_are_not_expanded_again() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let payload = json!({
+            "tab_position": 0,
+            "name": "{a}",
+            "vars": {"a": "{b}", "b": "{a}"},
+        });
+        let response = pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(response["name"], "{b}");
+    }
+
+    #[test]
+    fn rename_dynamic_renders_aggregate_vars_once() {
+        let panes = vec![(0, vec![terminal(1), terminal(2)]), (1, vec![terminal(3)])];
+        let mut state = state(tabs(&["a", "b"]), panes);
+        let payload = json!({
+            "tab_position": 1,
+            "name": "{active_tab_name}@{active_tab_position} {total_tabs}/{total_panes} [{tab_names}]",
+        });
+        pipe(&mut state, "rename-dynamic", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "a@1 2/3 [a, b]"]);
+
+        // The name is frozen, so a new pane doesn't re-render it
+        let panes = vec![
+            (0, vec![terminal(1), terminal(2), terminal(4)]),
+            (1, vec![terminal(3)]),
+        ];
+        state.update(Event::PaneUpdate(manifest(panes)));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "a@1 2/3 [a, b]"]);
+    }
+
+    #[test]
+    fn rename_dynamic_vars_colliding_with_aggregates() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let payload =
+            json!({"tab_position": 0, "name": "{total_tabs}", "vars": {"total_tabs": "9"}});
+        let response = pipe(&mut state, "rename-dynamic", payload).unwrap();
+        assert_eq!(response["name"], "1");
+
+        let payload = json!({
+            "tab_position": 0,
+            "name": "{total_tabs}",
+            "vars": {"total_tabs": "9"},
+            "strict": true,
+        });
+        assert_eq!(
+            error_code(pipe(&mut state, "rename-dynamic", payload)),
+            "RESERVED_VAR"
+        );
+    }
+
+    #[test]
+    fn truncate_modes_shorten_long_names() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let mut rename = |payload: serde_json::Value| {
+            pipe(&mut state, "change-tab-name", payload).unwrap()["name"].clone()
+        };
+        let name = "path/to/project";
+        let payload = json!({"tab_position": 0, "name": name, "max_columns": 12});
+        assert_eq!(rename(payload), "path/to/proj");
+        let payload = json!({"tab_position": 0, "name": name, "max_columns": 12, "truncate_mode": "segments"});
+        assert_eq!(rename(payload), "…/to/project");
+        let payload = json!({"tab_position": 0, "name": name, "max_columns": 15, "truncate_mode": "segments"});
+        assert_eq!(rename(payload), name);
+        let payload = json!({
+            "tab_position": 0,
+            "name": "a > b > c",
+            "max_columns": 5,
+            "truncate_mode": "segments",
+            "truncate_separator": " > ",
+        });
+        assert_eq!(rename(payload), "… > c");
+    }
+
+    #[test]
+    fn truncate_to_segments_falls_back_to_columns() {
+        assert_eq!(
+            truncate_to_segments("path/to/project", 11, "/"),
+            "…/project"
+        );
+        // Not even the last segment fits
+        assert_eq!(truncate_to_segments("path/to/project", 5, "/"), "path/");
+        assert_eq!(truncate_to_segments("project-name", 7, "/"), "project");
+        assert_eq!(truncate_to_segments("path/to", 4, ""), "path");
+        // Wide characters count as two columns and are never split
+        assert_eq!(truncate_to_segments("日本/語", 4, "/"), "…/語");
+        assert_eq!(truncate_to_columns("日本語", 5), "日本");
+    }
+
+    #[test]
+    fn tabs_reported_out_of_order_are_sorted_by_position() {
+        let panes = vec![
+            (0, vec![terminal(1)]),
+            (1, vec![terminal(2)]),
+            (2, vec![terminal(3)]),
+        ];
+        let mut state = state(tabs(&["a", "b", "c"]), panes);
+        let ids: Vec<_> = (0..3).map(|p| state.stable_tab_id_at(p)).collect();
+
+        state.update(Event::TabUpdate(vec![
+            tab(2, "c", false),
+            tab(0, "a", true),
+            tab(1, "b", false),
+        ]));
+        assert_eq!(tab_names(&state), ["a", "b", "c"]);
+        assert_eq!(state.pane_to_tab.get(&3), Some(&2));
+        let ids_after: Vec<_> = (0..3).map(|p| state.stable_tab_id_at(p)).collect();
+        assert_eq!(ids_after, ids);
+
+        let payload = json!({"pane_id": "3", "name": "x"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "b", "x"]);
+    }
+
+    #[test]
+    fn swap_names_exchanges_names_and_formats() {
+        let panes = vec![(0, vec![terminal(1)]), (1, vec![terminal(2), terminal(3)])];
+        let mut state = state(tabs(&["a", "b"]), panes);
+        let payload = json!({"tab_position": 0, "name": "one {pane_count}"});
+        pipe(&mut state, "change-tab-name", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["one 1", "b"]);
+
+        let payload = json!({"first": {"pane_id": "1"}, "second": {"tab_position": 1}});
+        let response = pipe(&mut state, "swap-names", payload).unwrap();
+        assert_eq!(response["tabs"][0]["old_name"], "one 1");
+        assert_eq!(response["tabs"][0]["name"], "b");
+        assert_eq!(response["tabs"][1]["name"], "one 2");
+        apply_renames(&mut state);
+        // The format moved with the name and renders for its new tab
+        assert_eq!(tab_names(&state), ["b", "one 2"]);
+
+        let payload = json!({"first": {"tab_position": 0}, "second": {"pane_id": "1"}});
+        assert_eq!(
+            error_code(pipe(&mut state, "swap-names", payload)),
+            "SAME_TAB"
+        );
+        let payload = json!({"first": {"tab_position": 0}, "second": {"tab_position": 5}});
+        assert_eq!(
+            error_code(pipe(&mut state, "swap-names", payload)),
+            "TAB_NOT_FOUND"
+        );
+    }
+
+    #[test]
+    fn deferred_rename_applies_once_the_tab_is_active() {
+        let panes = vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])];
+        let mut state = state(tabs(&["a", "b"]), panes);
+        let payload = json!({"tab_position": 1, "name": "later", "defer_until_active": true});
+        let response = pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert_eq!(response["status"], "DEFERRED");
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "b"]);
+
+        state.update(Event::TabUpdate(vec![
+            tab(0, "a", false),
+            tab(1, "b", true),
+        ]));
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "later"]);
+        assert!(state.deferred_until_active.is_empty());
+
+        // The active tab is renamed right away
+        let payload = json!({"tab_position": 1, "name": "now", "defer_until_active": true});
+        let response = pipe(&mut state, "change-tab-name", payload).unwrap();
+        assert!(response.get("status").is_none());
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a", "now"]);
+    }
+
+    #[test]
+    fn glob_match_is_anchored_and_backtracks() {
+        let cases = [
+            ("*", "", true),
+            ("*", "anything", true),
+            ("?", "", false),
+            ("?", "é", true),
+            ("main", "main", true),
+            ("main", "main2", false),
+            ("ma*", "main", true),
+            ("*in", "main", true),
+            ("m*n", "mn", true),
+            ("*a*b", "xaxxab", true),
+            ("*a*b", "xaxxa", false),
+            ("a?c*", "abcdef", true),
+            ("a?c*", "ac", false),
+            ("**", "x", true),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                glob_match(pattern, text),
+                expected,
+                "{} ~ {}",
+                pattern,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn format_number_pads_and_rounds() {
+        let cases = [
+            ("7", "03", "007"),
+            ("7", "3", "  7"),
+            ("3.14159", ".2", "3.14"),
+            ("3.14159", "06.1", "0003.1"),
+            ("1.50", "", "1.5"),
+            (" 42 ", "", "42"),
+            ("-1.5", "05", "-01.5"),
+            ("12345", "03", "12345"),
+        ];
+        for (value, spec, expected) in cases {
+            assert_eq!(
+                format_number(value, spec).unwrap(),
+                expected,
+                "{}:{}",
+                value,
+                spec
+            );
+        }
+
+        assert!(format_number("abc", "03").is_err());
+        assert!(format_number("1", "x3").is_err());
+        assert!(format_number("1", "3.").is_err());
+        assert!(format_number("1", "257").is_err());
+        assert!(format_number("1", ".257").is_err());
+        assert_eq!(format_number("1", "0256").unwrap().len(), 256);
+    }
+
+    #[test]
+    fn custom_delimiters_translate_to_braces() {
+        assert_eq!(translate_delimiters("<<a>> {b}", "<<", ">>"), "{a} {{b}}");
+        assert_eq!(
+            translate_delimiters("[[name:>5]]-é", "[[", "]]"),
+            "{name:>5}-é"
+        );
+        assert_eq!(translate_delimiters("plain", "%(", ")"), "plain");
+
+        let configuration = |open: &str, close: &str| {
+            BTreeMap::from([
+                ("delim_open".to_string(), open.to_string()),
+                ("delim_close".to_string(), close.to_string()),
+            ])
+        };
+        let mut warnings = Vec::new();
+        assert_eq!(
+            parse_delimiters(&configuration("<<", ">>"), &mut warnings),
+            Some(("<<".to_string(), ">>".to_string()))
+        );
+        assert_eq!(
+            parse_delimiters(&configuration("{", "}"), &mut warnings),
+            None
+        );
+        assert!(warnings.is_empty());
+        for (open, close) in [("%", "%"), ("", ">>")] {
+            assert_eq!(
+                parse_delimiters(&configuration(open, close), &mut warnings),
+                None
+            );
+        }
+        let only_open = BTreeMap::from([("delim_open".to_string(), "<<".to_string())]);
+        assert_eq!(parse_delimiters(&only_open, &mut warnings), None);
+        assert_eq!(warnings.len(), 3);
+    }
+
+    #[test]
+    fn cwd_basename_handles_trailing_slashes_and_root() {
+        assert_eq!(cwd_basename("/home/user/project"), "project");
+        assert_eq!(cwd_basename("/home/user/project//"), "project");
+        assert_eq!(cwd_basename("relative"), "relative");
+        assert_eq!(cwd_basename("/"), "/");
+        assert_eq!(cwd_basename("///"), "/");
+        assert_eq!(cwd_basename(""), "");
+    }
+
+    #[test]
+    fn common_directory_compares_whole_components() {
+        let common = |cwds: &[&str]| common_directory(cwds.iter().copied());
+        assert_eq!(common(&[]), None);
+        assert_eq!(common(&["/src/app/"]).as_deref(), Some("/src/app"));
+        assert_eq!(
+            common(&["/src/app/web", "/src/app//api", "/src/app"]).as_deref(),
+            Some("/src/app")
+        );
+        // `/src/app` and `/src/apple` only share `/src`
+        assert_eq!(common(&["/src/app", "/src/apple"]).as_deref(), Some("/src"));
+        assert_eq!(common(&["/home", "/tmp"]).as_deref(), Some("/"));
+    }
+
+    #[test]
+    fn binary_frames_decode_in_order_and_reject_malformed_payloads() {
+        let mut payload = binary_frame(1, "first");
+        payload.extend(binary_frame(u32::MAX, "日本"));
+        payload.extend(binary_frame(3, ""));
+        let frames = decode_binary_frames(&payload).unwrap();
+        assert_eq!(frames, [(1, "first"), (u32::MAX, "日本"), (3, "")]);
+
+        let invalid = |payload: &[u8]| match decode_binary_frames(payload) {
+            Err(error) => error.code,
+            Ok(frames) => panic!("decoded {:?}", frames),
+        };
+        assert_eq!(invalid(&[]), "INVALID_BINARY");
+        // Header cut short, name cut short
+        assert_eq!(invalid(&binary_frame(1, "name")[..7]), "INVALID_BINARY");
+        assert_eq!(invalid(&binary_frame(1, "name")[..12]), "INVALID_BINARY");
+        // A header byte with the high bit set and a value above u32::MAX
+        let mut high_bit = binary_frame(1, "a");
+        high_bit[0] = 0x80;
+        assert_eq!(invalid(&high_bit), "INVALID_BINARY");
+        let mut too_large = binary_frame(1, "a");
+        too_large[0] = 0x7f;
+        assert_eq!(invalid(&too_large), "INVALID_BINARY");
+        let mut not_utf8 = binary_frame(1, "a");
+        not_utf8[10] = 0xff;
+        assert_eq!(invalid(&not_utf8), "INVALID_BINARY");
+    }
+
+    #[test]
+    fn pane_count_conditions_parse_and_match() {
+        let matching = |condition: &str| -> Vec<usize> {
+            let condition: PaneCountCondition = condition.parse().unwrap();
+            (0..5).filter(|&n| condition.matches(n)).collect()
+        };
+        assert_eq!(matching("2"), [2]);
+        assert_eq!(matching(">2"), [3, 4]);
+        assert_eq!(matching(">=2"), [2, 3, 4]);
+        assert_eq!(matching("<2"), [0, 1]);
+        assert_eq!(matching("<=2"), [0, 1, 2]);
+        assert_eq!(matching(" >= 3 "), [3, 4]);
+
+        for condition in ["", ">", "=2", "two", "-1", "=>2"] {
+            assert!(
+                condition.parse::<PaneCountCondition>().is_err(),
+                "{:?}",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn color_rules_are_rejected_instead_of_ignored() {
+        let mut state = state(tabs(&["a"]), vec![(0, vec![terminal(1)])]);
+        let payload = json!({
+            "tab_position": 0,
+            "name": "build",
+            "color_rules": [{"var": "pane_exit_code", "op": "!=", "value": "0", "color": "red"}],
+        });
+        assert_eq!(
+            error_code(pipe(&mut state, "change-tab-name", payload)),
+            "UNSUPPORTED"
+        );
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["a"]);
+    }
+
+    #[test]
+    fn rename_all_reports_each_entry_and_keeps_going() {
+        let panes = vec![
+            (0, vec![terminal(1)]),
+            (1, vec![terminal(2)]),
+            (2, vec![terminal(3)]),
+        ];
+        let mut state = state(tabs(&["a", "b", "c"]), panes);
+        let payload = json!({"names": ["{tab_position}: x", "{unclosed", "c", "ignored"]});
+        let response = pipe(&mut state, "rename-all", payload).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["1: x", "b", "c"]);
+
+        // The third tab already had its name, so only the first counts
+        assert_eq!(response["applied"], 1);
+        let results = response["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["changed"], true);
+        assert_eq!(results[1]["ok"], false);
+        assert_eq!(results[1]["code"], "INVALID_FORMAT");
+        assert_eq!(results[1]["tab_position"], 1);
+        assert_eq!(results[2]["changed"], false);
+    }
+
+    #[test]
+    fn shift_renumber_reports_tabs_that_fail_to_render() {
+        let panes = vec![
+            (0, vec![terminal(1)]),
+            (1, vec![terminal(2)]),
+            (2, vec![terminal(3)]),
+        ];
+        let mut state = state(tabs(&["a", "b", "c"]), panes);
+        // Overflows from the second tab on
+        let format = format!("{{tab_position+{}}}", i64::MAX - 1);
+        let payload = json!({"start_position": 0, "format": format});
+        let response = pipe(&mut state, "shift-renumber", payload).unwrap();
+        apply_renames(&mut state);
+
+        assert_eq!(response["renamed"], json!([i64::MAX.to_string()]));
+        let failed = response["failed"].as_array().unwrap();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0]["tab_position"], 1);
+        assert_eq!(failed[0]["code"], "INVALID_FORMAT");
+        assert_eq!(tab_names(&state), [i64::MAX.to_string().as_str(), "b", "c"]);
+
+        let payload = json!({"start_position": 1, "format": "{tab_position}: work"});
+        let response = pipe(&mut state, "shift-renumber", payload).unwrap();
+        assert_eq!(response["renamed"], json!(["2: work", "3: work"]));
+        assert_eq!(response["failed"], json!([]));
+    }
+
+    #[test]
+    fn binary_frames_for_unknown_panes_are_reported_without_stopping() {
+        let panes = vec![(0, vec![terminal(1)]), (1, vec![terminal(2)])];
+        let mut state = state(tabs(&["a", "b"]), panes);
+        let mut payload = binary_frame(1, "first");
+        payload.extend(binary_frame(42, "unknown"));
+        payload.extend(binary_frame(2, "second"));
+        let payload = String::from_utf8(payload).unwrap();
+
+        let response = state.change_tab_name_bin(Some(&payload)).unwrap();
+        apply_renames(&mut state);
+        assert_eq!(tab_names(&state), ["first", "second"]);
+        assert_eq!(response["applied"], 2);
+        assert_eq!(
+            response["failed"],
+            json!([{
+                "frame": 1,
+                "pane_id": 42,
+                "code": "PANE_NOT_FOUND",
+                "error": response["failed"][0]["error"],
+            }])
+        );
+    }
+
+    #[test]
+    fn stable_id_lookups_follow_set_stable_id_and_resync() {
+        let panes = vec![(0, vec![terminal(1), terminal(2)]), (1, vec![terminal(3)])];
+        let mut state = state(tabs(&["a", "b"]), panes);
+        assert_eq!(state.stable_tab_id_at(0), Some(1));
+        assert_eq!(state.stable_tab_id_at(2), None);
+
+        let payload = json!({"pane_id": "2", "stable_id": 7});
+        pipe(&mut state, "set-stable-id", payload).unwrap();
+        assert_eq!(state.stable_tab_id_at(0), Some(7));
+        pipe(&mut state, "rename-focused-tab", json!({"name": "x"})).unwrap();
+        assert_eq!(state.last_emitted_name(7).map(String::as_str), Some("x"));
+
+        state.resync_ids();
+        assert_eq!(state.stable_tab_id_at(0), Some(1));
+        assert_eq!(state.stable_tab_id_at(1), Some(2));
+    }
+
+    /// Timing checks, run with
+    /// `cargo test --release --features bench --target <host> bench::`
+    #[cfg(feature = "bench")]
+    mod bench {
+        use super::*;
+
+        /// 50 tabs with 10 terminal panes each
+        fn large_manifest() -> (Vec<TabInfo>, Vec<(usize, Vec<PaneInfo>)>) {
+            let tab_list = (0..50)
+                .map(|p| tab(p, &format!("tab {}", p), p == 0))
+                .collect();
+            let panes = (0..50)
+                .map(|p| (p, (0..10).map(|i| terminal((p * 10 + i) as u32)).collect()))
+                .collect();
+            (tab_list, panes)
+        }
+
+        #[test]
+        fn rebuild_of_500_panes_stays_within_budget() {
+            let (tab_list, panes) = large_manifest();
+            let mut state = state(tab_list, panes);
+
+            let start = Instant::now();
+            for _ in 0..100 {
+                state.rebuild_pane_to_tab();
+            }
+            let elapsed = start.elapsed();
+            println!("100 rebuilds of 500 panes: {:?}", elapsed);
+            assert_eq!(state.pane_to_stable_tab_id.len(), 500);
+            assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+        }
+
+        #[test]
+        fn binary_payloads_parse_faster_than_json() {
+            const RENAMES: u32 = 10_000;
+            let json_payloads: Vec<String> = (0..RENAMES)
+                .map(|i| {
+                    json!({"pane_id": (i % 500).to_string(), "name": format!("build {}", i)})
+                        .to_string()
+                })
+                .collect();
+            let binary_payloads: Vec<String> = (0..RENAMES)
+                .map(|i| String::from_utf8(binary_frame(i % 500, &format!("build {}", i))).unwrap())
+                .collect();
+
+            let start = Instant::now();
+            for payload in &json_payloads {
+                parse_rename_payload(Some(payload.clone())).unwrap();
+            }
+            let json_elapsed = start.elapsed();
+
+            let start = Instant::now();
+            for payload in &binary_payloads {
+                decode_binary_frames(payload.as_bytes()).unwrap();
+            }
+            let binary_elapsed = start.elapsed();
+
+            println!(
+                "{} payloads: JSON {:?}, binary {:?}",
+                RENAMES, json_elapsed, binary_elapsed
+            );
+            assert!(binary_elapsed < json_elapsed);
+
+            // End to end, including resolving and renaming the tab
+            let (tab_list, panes) = large_manifest();
+            let mut state = state(tab_list, panes);
+            let start = Instant::now();
+            for payload in json_payloads {
+                state.change_tab_name(Some(payload)).unwrap();
+            }
+            let json_elapsed = start.elapsed();
+            let start = Instant::now();
+            for payload in &binary_payloads {
+                state.change_tab_name_bin(Some(payload)).unwrap();
+            }
+            println!(
+                "{} renames: JSON {:?}, binary {:?}",
+                RENAMES,
+                json_elapsed,
+                start.elapsed()
+            );
+        }
     }
 }